@@ -0,0 +1,83 @@
+#ifndef TRACKLIB_H
+#define TRACKLIB_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TRACKLIB_SECTION_TRACK_POINTS 0
+#define TRACKLIB_SECTION_COURSE_POINTS 1
+/* sections are numbered 0..TRACKLIB_SECTION_COUNT */
+#define TRACKLIB_SECTION_COUNT 2
+
+typedef enum TracklibStatus {
+  TRACKLIB_OK = 0,
+  TRACKLIB_NULL_POINTER = 1,
+  TRACKLIB_INVALID_UTF8 = 2,
+  TRACKLIB_UNKNOWN_SECTION = 3,
+  TRACKLIB_PARSE_ERROR = 4,
+  TRACKLIB_WRITE_ERROR = 5,
+  TRACKLIB_ADD_POINT_ERROR = 6,
+  TRACKLIB_NOT_FOUND = 7,
+  TRACKLIB_TYPE_MISMATCH = 8,
+  TRACKLIB_PANIC = 9,
+} TracklibStatus;
+
+typedef enum TracklibColumnType {
+  TRACKLIB_COLUMN_NUMBERS = 0,
+  TRACKLIB_COLUMN_LONG_FLOAT = 1,
+  TRACKLIB_COLUMN_SHORT_FLOAT = 2,
+  TRACKLIB_COLUMN_BASE64 = 3,
+  TRACKLIB_COLUMN_STRING = 4,
+  TRACKLIB_COLUMN_BOOL = 5,
+  TRACKLIB_COLUMN_IDS = 6,
+} TracklibColumnType;
+
+typedef enum TracklibTrackType {
+  TRACKLIB_TRACK_TYPE_TRIP = 0,
+  TRACKLIB_TRACK_TYPE_ROUTE = 1,
+  TRACKLIB_TRACK_TYPE_SEGMENT = 2,
+} TracklibTrackType;
+
+typedef struct RWTFile RWTFile;
+
+/* Lifecycle */
+RWTFile *tracklib_rwtf_new(void);
+TracklibStatus tracklib_rwtf_from_bytes(const uint8_t *bytes, size_t len, RWTFile **out);
+void tracklib_rwtf_free(RWTFile *rwtf);
+TracklibStatus tracklib_rwtf_write(const RWTFile *rwtf, uint8_t **out, size_t *out_len);
+void tracklib_bytes_free(uint8_t *bytes, size_t len);
+
+/* Write */
+TracklibStatus tracklib_rwtf_add_number(RWTFile *rwtf, uint32_t section, size_t index, const char *name, int64_t v);
+TracklibStatus tracklib_rwtf_add_long_float(RWTFile *rwtf, uint32_t section, size_t index, const char *name, double v);
+TracklibStatus tracklib_rwtf_add_short_float(RWTFile *rwtf, uint32_t section, size_t index, const char *name, double v);
+TracklibStatus tracklib_rwtf_add_string(RWTFile *rwtf, uint32_t section, size_t index, const char *name, const char *v);
+TracklibStatus tracklib_rwtf_add_bytes(RWTFile *rwtf, uint32_t section, size_t index, const char *name, const uint8_t *bytes, size_t len);
+TracklibStatus tracklib_rwtf_add_bool(RWTFile *rwtf, uint32_t section, size_t index, const char *name, bool v);
+TracklibStatus tracklib_rwtf_add_ids(RWTFile *rwtf, uint32_t section, size_t index, const char *name, const uint64_t *ids, size_t len);
+
+/* Read - borrowed pointers are valid until the file is modified or freed */
+TracklibStatus tracklib_rwtf_section_len(const RWTFile *rwtf, uint32_t section, size_t *out);
+TracklibStatus tracklib_rwtf_column_count(const RWTFile *rwtf, uint32_t section, size_t *out);
+TracklibStatus tracklib_rwtf_column_name(const RWTFile *rwtf, uint32_t section, size_t column_index, const uint8_t **out, size_t *out_len);
+TracklibStatus tracklib_rwtf_column_type(const RWTFile *rwtf, uint32_t section, size_t column_index, TracklibColumnType *out);
+TracklibStatus tracklib_rwtf_metadata_created_at(const RWTFile *rwtf, uint64_t *out);
+TracklibStatus tracklib_rwtf_metadata_track_type(const RWTFile *rwtf, TracklibTrackType *out_type, uint32_t *out_id);
+TracklibStatus tracklib_rwtf_metadata_time_zone(const RWTFile *rwtf, int32_t *out_offset, const uint8_t **out_name, size_t *out_name_len);
+TracklibStatus tracklib_rwtf_get_number(const RWTFile *rwtf, uint32_t section, size_t index, const char *name, int64_t *out);
+TracklibStatus tracklib_rwtf_get_float(const RWTFile *rwtf, uint32_t section, size_t index, const char *name, double *out);
+TracklibStatus tracklib_rwtf_get_bool(const RWTFile *rwtf, uint32_t section, size_t index, const char *name, bool *out);
+TracklibStatus tracklib_rwtf_get_string(const RWTFile *rwtf, uint32_t section, size_t index, const char *name, const uint8_t **out, size_t *out_len);
+TracklibStatus tracklib_rwtf_get_bytes(const RWTFile *rwtf, uint32_t section, size_t index, const char *name, const uint8_t **out, size_t *out_len);
+TracklibStatus tracklib_rwtf_get_ids(const RWTFile *rwtf, uint32_t section, size_t index, const char *name, const uint64_t **out, size_t *out_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TRACKLIB_H */
@@ -0,0 +1,724 @@
+//! A C interface to tracklib.
+//!
+//! Files are handed out as opaque `RWTFile` pointers which must be released
+//! with `tracklib_rwtf_free`. Every fallible call returns a `TracklibStatus`
+//! and writes its result through an out pointer. The matching C header lives
+//! in `include/tracklib.h`.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::UNIX_EPOCH;
+use std::{ptr, slice};
+use tracklib::{parse_rwtf, Column, DataField, RWTFile, Section, TrackType};
+
+pub const TRACKLIB_SECTION_TRACK_POINTS: u32 = 0;
+pub const TRACKLIB_SECTION_COURSE_POINTS: u32 = 1;
+// sections are numbered 0..TRACKLIB_SECTION_COUNT
+pub const TRACKLIB_SECTION_COUNT: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TracklibStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    UnknownSection = 3,
+    ParseError = 4,
+    WriteError = 5,
+    AddPointError = 6,
+    NotFound = 7,
+    TypeMismatch = 8,
+    Panic = 9,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TracklibColumnType {
+    Numbers = 0,
+    LongFloat = 1,
+    ShortFloat = 2,
+    Base64 = 3,
+    String = 4,
+    Bool = 5,
+    IDs = 6,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TracklibTrackType {
+    Trip = 0,
+    Route = 1,
+    Segment = 2,
+}
+
+type Result<T> = std::result::Result<T, TracklibStatus>;
+
+// Runs the body of an entry point. Panics must not unwind into C, so they
+// are caught here and reported as a status instead.
+fn status<F: FnOnce() -> Result<()>>(f: F) -> TracklibStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => TracklibStatus::Ok,
+        Ok(Err(e)) => e,
+        Err(_) => TracklibStatus::Panic,
+    }
+}
+
+unsafe fn as_ref<'a, T>(p: *const T) -> Result<&'a T> {
+    p.as_ref().ok_or(TracklibStatus::NullPointer)
+}
+
+unsafe fn as_mut<'a, T>(p: *mut T) -> Result<&'a mut T> {
+    p.as_mut().ok_or(TracklibStatus::NullPointer)
+}
+
+unsafe fn as_str<'a>(s: *const c_char) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(TracklibStatus::NullPointer);
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| TracklibStatus::InvalidUtf8)
+}
+
+fn section(rwtf: &RWTFile, section: u32) -> Result<&Section> {
+    match section {
+        TRACKLIB_SECTION_TRACK_POINTS => Ok(&rwtf.track_points),
+        TRACKLIB_SECTION_COURSE_POINTS => Ok(&rwtf.course_points),
+        _ => Err(TracklibStatus::UnknownSection),
+    }
+}
+
+unsafe fn column_at<'a>(rwtf: *const RWTFile, section_type: u32, column_index: usize) -> Result<(&'a String, &'a Column)> {
+    let rwtf = as_ref(rwtf)?;
+    section(rwtf, section_type)?
+        .columns()
+        .iter()
+        .nth(column_index)
+        .ok_or(TracklibStatus::NotFound)
+}
+
+unsafe fn column<'a>(rwtf: *const RWTFile, section_type: u32, name: *const c_char) -> Result<&'a Column> {
+    let rwtf = as_ref(rwtf)?;
+    let name = as_str(name)?;
+    section(rwtf, section_type)?
+        .columns()
+        .get(name)
+        .ok_or(TracklibStatus::NotFound)
+}
+
+unsafe fn add_point(rwtf: *mut RWTFile, section_type: u32, index: usize, name: *const c_char, v: DataField) -> Result<()> {
+    let rwtf = as_mut(rwtf)?;
+    let name = as_str(name)?;
+    let r = match section_type {
+        TRACKLIB_SECTION_TRACK_POINTS => rwtf.add_track_point(index, name, v),
+        TRACKLIB_SECTION_COURSE_POINTS => rwtf.add_course_point(index, name, v),
+        _ => return Err(TracklibStatus::UnknownSection),
+    };
+    r.map_err(|_| TracklibStatus::AddPointError)
+}
+
+unsafe fn write_out<T>(out: *mut T, v: T) -> Result<()> {
+    if out.is_null() {
+        return Err(TracklibStatus::NullPointer);
+    }
+    ptr::write(out, v);
+    Ok(())
+}
+
+unsafe fn write_slice<T>(out: *mut *const T, out_len: *mut usize, v: &[T]) -> Result<()> {
+    if out_len.is_null() {
+        return Err(TracklibStatus::NullPointer);
+    }
+    write_out(out, v.as_ptr())?;
+    write_out(out_len, v.len())
+}
+
+//////////////////////////////
+//        Lifecycle         //
+//////////////////////////////
+
+/// Returns a new, empty file.
+#[no_mangle]
+pub extern "C" fn tracklib_rwtf_new() -> *mut RWTFile {
+    panic::catch_unwind(|| Box::into_raw(Box::new(RWTFile::new()))).unwrap_or(ptr::null_mut())
+}
+
+/// Parses `len` bytes at `bytes` and stores the new file in `*out`.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_from_bytes(bytes: *const u8, len: usize, out: *mut *mut RWTFile) -> TracklibStatus {
+    status(|| {
+        if bytes.is_null() || out.is_null() {
+            return Err(TracklibStatus::NullPointer);
+        }
+        let (_, rwtf) = parse_rwtf(slice::from_raw_parts(bytes, len)).map_err(|_| TracklibStatus::ParseError)?;
+        write_out(out, Box::into_raw(Box::new(rwtf)))
+    })
+}
+
+/// Releases a file returned by `tracklib_rwtf_new` or `tracklib_rwtf_from_bytes`.
+///
+/// # Safety
+///
+/// `rwtf` must be null or a pointer obtained from this library which hasn't
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_free(rwtf: *mut RWTFile) {
+    if !rwtf.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(rwtf))));
+    }
+}
+
+/// Serializes the file into a newly allocated buffer which must be released
+/// with `tracklib_bytes_free`.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out`/`out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_write(rwtf: *const RWTFile, out: *mut *mut u8, out_len: *mut usize) -> TracklibStatus {
+    status(|| {
+        let rwtf = as_ref(rwtf)?;
+        if out.is_null() || out_len.is_null() {
+            return Err(TracklibStatus::NullPointer);
+        }
+        let mut buf = Vec::new();
+        rwtf.write(&mut buf).map_err(|_| TracklibStatus::WriteError)?;
+        let buf = buf.into_boxed_slice();
+        write_out(out_len, buf.len())?;
+        write_out(out, Box::into_raw(buf) as *mut u8)
+    })
+}
+
+/// Releases a buffer returned by `tracklib_rwtf_write`.
+///
+/// # Safety
+///
+/// `bytes` and `len` must be exactly what `tracklib_rwtf_write` returned.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_bytes_free(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)))));
+    }
+}
+
+//////////////////////////////
+//          Write           //
+//////////////////////////////
+
+/// # Safety
+///
+/// `rwtf` must be a valid file and `name` a NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_add_number(rwtf: *mut RWTFile, section: u32, index: usize, name: *const c_char, v: i64) -> TracklibStatus {
+    status(|| add_point(rwtf, section, index, name, DataField::Number(v)))
+}
+
+/// # Safety
+///
+/// `rwtf` must be a valid file and `name` a NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_add_long_float(rwtf: *mut RWTFile, section: u32, index: usize, name: *const c_char, v: f64) -> TracklibStatus {
+    status(|| add_point(rwtf, section, index, name, DataField::LongFloat(v)))
+}
+
+/// # Safety
+///
+/// `rwtf` must be a valid file and `name` a NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_add_short_float(rwtf: *mut RWTFile, section: u32, index: usize, name: *const c_char, v: f64) -> TracklibStatus {
+    status(|| add_point(rwtf, section, index, name, DataField::ShortFloat(v)))
+}
+
+/// # Safety
+///
+/// `rwtf` must be a valid file and `name` and `v` NUL-terminated UTF-8 strings.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_add_string(rwtf: *mut RWTFile, section: u32, index: usize, name: *const c_char, v: *const c_char) -> TracklibStatus {
+    status(|| add_point(rwtf, section, index, name, DataField::String(as_str(v)?.to_string())))
+}
+
+/// Adds a Base64 value from its raw (not yet encoded) bytes.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `bytes` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_add_bytes(rwtf: *mut RWTFile, section: u32, index: usize, name: *const c_char, bytes: *const u8, len: usize) -> TracklibStatus {
+    status(|| {
+        if bytes.is_null() {
+            return Err(TracklibStatus::NullPointer);
+        }
+        add_point(rwtf, section, index, name, DataField::Base64(base64::encode(slice::from_raw_parts(bytes, len))))
+    })
+}
+
+/// # Safety
+///
+/// `rwtf` must be a valid file and `name` a NUL-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_add_bool(rwtf: *mut RWTFile, section: u32, index: usize, name: *const c_char, v: bool) -> TracklibStatus {
+    status(|| add_point(rwtf, section, index, name, DataField::Bool(v)))
+}
+
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `ids` must point to `len` readable values.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_add_ids(rwtf: *mut RWTFile, section: u32, index: usize, name: *const c_char, ids: *const u64, len: usize) -> TracklibStatus {
+    status(|| {
+        if ids.is_null() {
+            return Err(TracklibStatus::NullPointer);
+        }
+        add_point(rwtf, section, index, name, DataField::IDs(slice::from_raw_parts(ids, len).to_vec()))
+    })
+}
+
+//////////////////////////////
+//           Read           //
+//////////////////////////////
+
+/// Stores the number of points in `section` in `*out`.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_section_len(rwtf: *const RWTFile, section_type: u32, out: *mut usize) -> TracklibStatus {
+    status(|| write_out(out, section(as_ref(rwtf)?, section_type)?.len()))
+}
+
+/// Stores the number of columns in `section` in `*out`.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_column_count(rwtf: *const RWTFile, section_type: u32, out: *mut usize) -> TracklibStatus {
+    status(|| write_out(out, section(as_ref(rwtf)?, section_type)?.columns().len()))
+}
+
+/// Borrows the name of the column at `column_index` (columns are ordered by
+/// name) as UTF-8 bytes (not NUL-terminated). The pointer is valid until the
+/// file is modified or freed.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out`/`out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_column_name(rwtf: *const RWTFile, section_type: u32, column_index: usize, out: *mut *const u8, out_len: *mut usize) -> TracklibStatus {
+    status(|| {
+        let (name, _) = column_at(rwtf, section_type, column_index)?;
+        write_slice(out, out_len, name.as_bytes())
+    })
+}
+
+/// Stores the type of the column at `column_index` in `*out`.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_column_type(rwtf: *const RWTFile, section_type: u32, column_index: usize, out: *mut TracklibColumnType) -> TracklibStatus {
+    status(|| {
+        let (_, column) = column_at(rwtf, section_type, column_index)?;
+        let column_type = match column {
+            Column::Numbers(_) => TracklibColumnType::Numbers,
+            Column::LongFloat(_) => TracklibColumnType::LongFloat,
+            Column::ShortFloat(_) => TracklibColumnType::ShortFloat,
+            Column::Base64(_) => TracklibColumnType::Base64,
+            Column::String(_) => TracklibColumnType::String,
+            Column::Bool(_) => TracklibColumnType::Bool,
+            Column::IDs(_) => TracklibColumnType::IDs,
+        };
+        write_out(out, column_type)
+    })
+}
+
+/// Stores the file's creation time, in seconds since the unix epoch, in
+/// `*out`. Returns `TRACKLIB_NOT_FOUND` if the file doesn't record one.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_metadata_created_at(rwtf: *const RWTFile, out: *mut u64) -> TracklibStatus {
+    status(|| {
+        if out.is_null() {
+            return Err(TracklibStatus::NullPointer);
+        }
+        let created_at = as_ref(rwtf)?.metadata().created_at().ok_or(TracklibStatus::NotFound)?;
+        let seconds = created_at.duration_since(UNIX_EPOCH).map_err(|_| TracklibStatus::NotFound)?.as_secs();
+        write_out(out, seconds)
+    })
+}
+
+/// Stores the file's track type and id. Returns `TRACKLIB_NOT_FOUND` if the
+/// file doesn't record one.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out_type`/`out_id` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_metadata_track_type(rwtf: *const RWTFile, out_type: *mut TracklibTrackType, out_id: *mut u32) -> TracklibStatus {
+    status(|| {
+        if out_id.is_null() {
+            return Err(TracklibStatus::NullPointer);
+        }
+        let track_type = as_ref(rwtf)?.metadata().track_type().ok_or(TracklibStatus::NotFound)?;
+        let (t, id) = match track_type {
+            TrackType::Trip(id) => (TracklibTrackType::Trip, id),
+            TrackType::Route(id) => (TracklibTrackType::Route, id),
+            TrackType::Segment(id) => (TracklibTrackType::Segment, id),
+        };
+        write_out(out_type, t)?;
+        write_out(out_id, id)
+    })
+}
+
+/// Stores the file's time zone: its UTC offset in seconds, and a borrowed
+/// IANA name as UTF-8 bytes (not NUL-terminated), which is null with a length
+/// of 0 if the file doesn't record one. Returns `TRACKLIB_NOT_FOUND` if the
+/// file has no time zone.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file and `out_offset`/`out_name`/`out_name_len`
+/// must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_metadata_time_zone(rwtf: *const RWTFile, out_offset: *mut i32, out_name: *mut *const u8, out_name_len: *mut usize) -> TracklibStatus {
+    status(|| {
+        if out_offset.is_null() || out_name.is_null() || out_name_len.is_null() {
+            return Err(TracklibStatus::NullPointer);
+        }
+        let time_zone = as_ref(rwtf)?.metadata().time_zone().ok_or(TracklibStatus::NotFound)?;
+        write_out(out_offset, time_zone.utc_offset())?;
+        match time_zone.name() {
+            Some(name) => write_slice(out_name, out_name_len, name.as_bytes()),
+            None => {
+                write_out(out_name, ptr::null())?;
+                write_out(out_name_len, 0)
+            }
+        }
+    })
+}
+
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_get_number(rwtf: *const RWTFile, section: u32, index: usize, name: *const c_char, out: *mut i64) -> TracklibStatus {
+    status(|| match column(rwtf, section, name)? {
+        Column::Numbers(m) => m.get(&index).ok_or(TracklibStatus::NotFound).and_then(|v| write_out(out, *v)),
+        _ => Err(TracklibStatus::TypeMismatch),
+    })
+}
+
+/// Reads either a LongFloat or a ShortFloat value.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_get_float(rwtf: *const RWTFile, section: u32, index: usize, name: *const c_char, out: *mut f64) -> TracklibStatus {
+    status(|| match column(rwtf, section, name)? {
+        Column::LongFloat(m) | Column::ShortFloat(m) => m.get(&index).ok_or(TracklibStatus::NotFound).and_then(|v| write_out(out, *v)),
+        _ => Err(TracklibStatus::TypeMismatch),
+    })
+}
+
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_get_bool(rwtf: *const RWTFile, section: u32, index: usize, name: *const c_char, out: *mut bool) -> TracklibStatus {
+    status(|| match column(rwtf, section, name)? {
+        Column::Bool(m) => m.get(&index).ok_or(TracklibStatus::NotFound).and_then(|v| write_out(out, *v)),
+        _ => Err(TracklibStatus::TypeMismatch),
+    })
+}
+
+/// Borrows a String value as UTF-8 bytes (not NUL-terminated). The pointer is
+/// valid until the file is modified or freed.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `out`/`out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_get_string(rwtf: *const RWTFile, section: u32, index: usize, name: *const c_char, out: *mut *const u8, out_len: *mut usize) -> TracklibStatus {
+    status(|| match column(rwtf, section, name)? {
+        Column::String(m) => m.get(&index).ok_or(TracklibStatus::NotFound).and_then(|v| write_slice(out, out_len, v.as_bytes())),
+        _ => Err(TracklibStatus::TypeMismatch),
+    })
+}
+
+/// Borrows the decoded bytes of a Base64 value. The pointer is valid until
+/// the file is modified or freed.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `out`/`out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_get_bytes(rwtf: *const RWTFile, section: u32, index: usize, name: *const c_char, out: *mut *const u8, out_len: *mut usize) -> TracklibStatus {
+    status(|| match column(rwtf, section, name)? {
+        Column::Base64(m) => m.get(&index).ok_or(TracklibStatus::NotFound).and_then(|v| write_slice(out, out_len, v)),
+        _ => Err(TracklibStatus::TypeMismatch),
+    })
+}
+
+/// Borrows an IDs value. The pointer is valid until the file is modified or
+/// freed.
+///
+/// # Safety
+///
+/// `rwtf` must be a valid file, `name` a NUL-terminated UTF-8 string and
+/// `out`/`out_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn tracklib_rwtf_get_ids(rwtf: *const RWTFile, section: u32, index: usize, name: *const c_char, out: *mut *const u64, out_len: *mut usize) -> TracklibStatus {
+    status(|| match column(rwtf, section, name)? {
+        Column::IDs(m) => m.get(&index).ok_or(TracklibStatus::NotFound).and_then(|v| write_slice(out, out_len, v)),
+        _ => Err(TracklibStatus::TypeMismatch),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    const TRACK: u32 = TRACKLIB_SECTION_TRACK_POINTS;
+    const COURSE: u32 = TRACKLIB_SECTION_COURSE_POINTS;
+
+    fn name(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    unsafe fn borrowed<'a, T>(p: *const T, len: usize) -> &'a [T] {
+        slice::from_raw_parts(p, len)
+    }
+
+    // builds a file with one value of every type, writes it and parses it back
+    unsafe fn roundtrip() -> *mut RWTFile {
+        let rwtf = tracklib_rwtf_new();
+        assert!(!rwtf.is_null());
+
+        let ids = [1u64, 2, 1 << 40];
+        assert_eq!(tracklib_rwtf_add_number(rwtf, TRACK, 0, name("n").as_ptr(), -42), TracklibStatus::Ok);
+        assert_eq!(tracklib_rwtf_add_long_float(rwtf, TRACK, 0, name("x").as_ptr(), -122.5), TracklibStatus::Ok);
+        assert_eq!(tracklib_rwtf_add_short_float(rwtf, TRACK, 1, name("d").as_ptr(), 2.5), TracklibStatus::Ok);
+        assert_eq!(tracklib_rwtf_add_string(rwtf, COURSE, 0, name("s").as_ptr(), name("hello").as_ptr()), TracklibStatus::Ok);
+        assert_eq!(tracklib_rwtf_add_bytes(rwtf, COURSE, 0, name("b").as_ptr(), [0u8, 1, 255].as_ptr(), 3), TracklibStatus::Ok);
+        assert_eq!(tracklib_rwtf_add_bool(rwtf, COURSE, 1, name("t").as_ptr(), true), TracklibStatus::Ok);
+        assert_eq!(tracklib_rwtf_add_ids(rwtf, COURSE, 1, name("i").as_ptr(), ids.as_ptr(), ids.len()), TracklibStatus::Ok);
+
+        let mut bytes = ptr::null_mut();
+        let mut len = 0;
+        assert_eq!(tracklib_rwtf_write(rwtf, &mut bytes, &mut len), TracklibStatus::Ok);
+        tracklib_rwtf_free(rwtf);
+
+        let mut parsed = ptr::null_mut();
+        assert_eq!(tracklib_rwtf_from_bytes(bytes, len, &mut parsed), TracklibStatus::Ok);
+        tracklib_bytes_free(bytes, len);
+        assert!(!parsed.is_null());
+        parsed
+    }
+
+    #[test]
+    fn test_roundtrip_values() {
+        unsafe {
+            let rwtf = roundtrip();
+
+            let mut len = 0;
+            assert_eq!(tracklib_rwtf_section_len(rwtf, TRACK, &mut len), TracklibStatus::Ok);
+            assert_eq!(len, 2);
+            assert_eq!(tracklib_rwtf_section_len(rwtf, COURSE, &mut len), TracklibStatus::Ok);
+            assert_eq!(len, 2);
+
+            let mut n = 0;
+            assert_eq!(tracklib_rwtf_get_number(rwtf, TRACK, 0, name("n").as_ptr(), &mut n), TracklibStatus::Ok);
+            assert_eq!(n, -42);
+
+            let mut f = 0.0;
+            assert_eq!(tracklib_rwtf_get_float(rwtf, TRACK, 0, name("x").as_ptr(), &mut f), TracklibStatus::Ok);
+            assert_eq!(f, -122.5);
+            assert_eq!(tracklib_rwtf_get_float(rwtf, TRACK, 1, name("d").as_ptr(), &mut f), TracklibStatus::Ok);
+            assert_eq!(f, 2.5);
+
+            let mut p = ptr::null();
+            let mut plen = 0;
+            assert_eq!(tracklib_rwtf_get_string(rwtf, COURSE, 0, name("s").as_ptr(), &mut p, &mut plen), TracklibStatus::Ok);
+            assert_eq!(borrowed(p, plen), b"hello");
+            assert_eq!(tracklib_rwtf_get_bytes(rwtf, COURSE, 0, name("b").as_ptr(), &mut p, &mut plen), TracklibStatus::Ok);
+            assert_eq!(borrowed(p, plen), &[0, 1, 255]);
+
+            let mut b = false;
+            assert_eq!(tracklib_rwtf_get_bool(rwtf, COURSE, 1, name("t").as_ptr(), &mut b), TracklibStatus::Ok);
+            assert!(b);
+
+            let mut ids = ptr::null();
+            assert_eq!(tracklib_rwtf_get_ids(rwtf, COURSE, 1, name("i").as_ptr(), &mut ids, &mut plen), TracklibStatus::Ok);
+            assert_eq!(borrowed(ids, plen), &[1, 2, 1 << 40]);
+
+            tracklib_rwtf_free(rwtf);
+        }
+    }
+
+    #[test]
+    fn test_enumerate_columns() {
+        unsafe {
+            let rwtf = roundtrip();
+
+            let mut count = 0;
+            assert_eq!(tracklib_rwtf_column_count(rwtf, TRACK, &mut count), TracklibStatus::Ok);
+            assert_eq!(count, 3);
+            assert_eq!(tracklib_rwtf_column_count(rwtf, COURSE, &mut count), TracklibStatus::Ok);
+            assert_eq!(count, 4);
+
+            let mut columns = vec![];
+            for i in 0..count {
+                let mut p = ptr::null();
+                let mut len = 0;
+                let mut column_type = TracklibColumnType::Numbers;
+                assert_eq!(tracklib_rwtf_column_name(rwtf, COURSE, i, &mut p, &mut len), TracklibStatus::Ok);
+                assert_eq!(tracklib_rwtf_column_type(rwtf, COURSE, i, &mut column_type), TracklibStatus::Ok);
+                columns.push((borrowed(p, len).to_vec(), column_type));
+            }
+            assert_eq!(columns, vec![(b"b".to_vec(), TracklibColumnType::Base64),
+                                     (b"i".to_vec(), TracklibColumnType::IDs),
+                                     (b"s".to_vec(), TracklibColumnType::String),
+                                     (b"t".to_vec(), TracklibColumnType::Bool)]);
+
+            let mut column_type = TracklibColumnType::Numbers;
+            assert_eq!(tracklib_rwtf_column_type(rwtf, TRACK, 3, &mut column_type), TracklibStatus::NotFound);
+            assert_eq!(tracklib_rwtf_column_count(rwtf, TRACKLIB_SECTION_COUNT, &mut count), TracklibStatus::UnknownSection);
+
+            tracklib_rwtf_free(rwtf);
+        }
+    }
+
+    #[test]
+    fn test_metadata() {
+        unsafe {
+            let rwtf = roundtrip();
+
+            let mut created_at = 0;
+            assert_eq!(tracklib_rwtf_metadata_created_at(rwtf, &mut created_at), TracklibStatus::Ok);
+            assert!(created_at > 0);
+
+            let mut track_type = TracklibTrackType::Trip;
+            let mut id = 0;
+            assert_eq!(tracklib_rwtf_metadata_track_type(rwtf, &mut track_type, &mut id), TracklibStatus::NotFound);
+
+            let mut offset = 0;
+            let mut p = ptr::null();
+            let mut len = 0;
+            assert_eq!(tracklib_rwtf_metadata_time_zone(rwtf, &mut offset, &mut p, &mut len), TracklibStatus::NotFound);
+            tracklib_rwtf_free(rwtf);
+
+            let mut f = RWTFile::with_track_type(TrackType::Route(7));
+            f.set_time_zone(Some(tracklib::TimeZone::new(3600, Some("Europe/Paris".to_string()))));
+            let rwtf = Box::into_raw(Box::new(f));
+
+            assert_eq!(tracklib_rwtf_metadata_track_type(rwtf, &mut track_type, &mut id), TracklibStatus::Ok);
+            assert_eq!((track_type, id), (TracklibTrackType::Route, 7));
+            assert_eq!(tracklib_rwtf_metadata_time_zone(rwtf, &mut offset, &mut p, &mut len), TracklibStatus::Ok);
+            assert_eq!(offset, 3600);
+            assert_eq!(borrowed(p, len), b"Europe/Paris");
+
+            tracklib_rwtf_free(rwtf);
+        }
+    }
+
+    #[test]
+    fn test_null_pointers() {
+        unsafe {
+            let rwtf = tracklib_rwtf_new();
+            let mut n = 0;
+            let mut out = ptr::null_mut();
+            let mut len = 0;
+            let bytes = [0u8; 4];
+
+            assert_eq!(tracklib_rwtf_from_bytes(ptr::null(), 0, &mut out), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_from_bytes(bytes.as_ptr(), bytes.len(), ptr::null_mut()), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_write(ptr::null(), &mut out as *mut _ as *mut *mut u8, &mut len), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_write(rwtf, ptr::null_mut(), &mut len), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_add_number(ptr::null_mut(), TRACK, 0, name("n").as_ptr(), 1), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_add_number(rwtf, TRACK, 0, ptr::null(), 1), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_add_string(rwtf, TRACK, 0, name("s").as_ptr(), ptr::null()), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_add_bytes(rwtf, TRACK, 0, name("b").as_ptr(), ptr::null(), 0), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_add_ids(rwtf, TRACK, 0, name("i").as_ptr(), ptr::null(), 0), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_section_len(rwtf, TRACK, ptr::null_mut()), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_column_count(ptr::null(), TRACK, &mut len), TracklibStatus::NullPointer);
+
+            assert_eq!(tracklib_rwtf_add_number(rwtf, TRACK, 0, name("n").as_ptr(), 1), TracklibStatus::Ok);
+            assert_eq!(tracklib_rwtf_get_number(rwtf, TRACK, 0, name("n").as_ptr(), ptr::null_mut()), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_get_number(rwtf, TRACK, 0, ptr::null(), &mut n), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_column_name(rwtf, TRACK, 0, ptr::null_mut(), &mut len), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_column_type(rwtf, TRACK, 0, ptr::null_mut()), TracklibStatus::NullPointer);
+            assert_eq!(tracklib_rwtf_metadata_created_at(rwtf, ptr::null_mut()), TracklibStatus::NullPointer);
+
+            // these accept null
+            tracklib_rwtf_free(ptr::null_mut());
+            tracklib_bytes_free(ptr::null_mut(), 0);
+            tracklib_rwtf_free(rwtf);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let rwtf = tracklib_rwtf_new();
+            let mut n = 0;
+            let mut f = 0.0;
+
+            assert_eq!(tracklib_rwtf_add_number(rwtf, 5, 0, name("n").as_ptr(), 1), TracklibStatus::UnknownSection);
+            assert_eq!(tracklib_rwtf_add_number(rwtf, TRACK, 0, name("n").as_ptr(), 1), TracklibStatus::Ok);
+            assert_eq!(tracklib_rwtf_add_number(rwtf, TRACK, 0, name("n").as_ptr(), 2), TracklibStatus::AddPointError);
+            assert_eq!(tracklib_rwtf_add_bool(rwtf, TRACK, 1, name("n").as_ptr(), true), TracklibStatus::AddPointError);
+            assert_eq!(tracklib_rwtf_add_long_float(rwtf, TRACK, 1, name("x").as_ptr(), f64::NAN), TracklibStatus::AddPointError);
+            assert_eq!(tracklib_rwtf_add_string(rwtf, TRACK, 1, [0xffu8, 0].as_ptr() as *const c_char, name("s").as_ptr()), TracklibStatus::InvalidUtf8);
+
+            assert_eq!(tracklib_rwtf_get_float(rwtf, TRACK, 0, name("n").as_ptr(), &mut f), TracklibStatus::TypeMismatch);
+            assert_eq!(tracklib_rwtf_get_number(rwtf, TRACK, 1, name("n").as_ptr(), &mut n), TracklibStatus::NotFound);
+            assert_eq!(tracklib_rwtf_get_number(rwtf, TRACK, 0, name("missing").as_ptr(), &mut n), TracklibStatus::NotFound);
+            assert_eq!(tracklib_rwtf_get_number(rwtf, 5, 0, name("n").as_ptr(), &mut n), TracklibStatus::UnknownSection);
+
+            tracklib_rwtf_free(rwtf);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        unsafe {
+            let rwtf = tracklib_rwtf_new();
+            assert_eq!(tracklib_rwtf_add_number(rwtf, TRACK, 0, name("n").as_ptr(), 1), TracklibStatus::Ok);
+            let mut bytes = ptr::null_mut();
+            let mut len = 0;
+            assert_eq!(tracklib_rwtf_write(rwtf, &mut bytes, &mut len), TracklibStatus::Ok);
+            tracklib_rwtf_free(rwtf);
+
+            let mut buf = borrowed(bytes, len).to_vec();
+            tracklib_bytes_free(bytes, len);
+
+            let mut out = ptr::null_mut();
+            assert_eq!(tracklib_rwtf_from_bytes(buf.as_ptr(), 10, &mut out), TracklibStatus::ParseError);
+            assert!(out.is_null());
+
+            // the decoder panics on continuation sections, which must not
+            // unwind into C
+            let data_offset = u16::from_le_bytes([buf[18], buf[19]]) as usize;
+            buf[data_offset] = 0x02;
+            assert_eq!(tracklib_rwtf_from_bytes(buf.as_ptr(), buf.len(), &mut out), TracklibStatus::Panic);
+            assert!(out.is_null());
+        }
+    }
+}
@@ -11,4 +11,10 @@ class RWTFile
     puts "I am a ruby method"
     return 5
   end
+
+  # Returns an array of row hashes for one section (0 = track points,
+  # 1 = course points), restricted to the given field names and row range.
+  def rows(section: 0, fields: nil, offset: 0, limit: nil)
+    rows_native(section, fields, offset, limit)
+  end
 end
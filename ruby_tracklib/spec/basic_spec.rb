@@ -47,6 +47,10 @@ describe RWTFile do
       orig_data = {"track_points"=>[{"x"=>10000000000000000000000000000000000.3, "y"=>7.2}]}
       expect(roundtrip(orig_data, CONFIG)).to eq({"track_points"=>[{"y"=>7.2}]})
     end
+    it "drops NaN" do
+      orig_data = {"track_points"=>[{"x"=>Float::NAN, "s"=>Float::NAN, "t"=>Float::NAN, "y"=>7.2}]}
+      expect(roundtrip(orig_data, CONFIG)).to eq({"track_points"=>[{"y"=>7.2}]})
+    end
     it "drops numbers that are valid primitives but still too large for tracklib" do
       ## Numbers
       # drops 2**60
@@ -83,3 +87,31 @@ describe RWTFile do
     end
   end
 end
+
+describe RWTFile do
+  context "rows" do
+    data = {"track_points"=>[{"t"=>1, "x"=>4.4, "y"=>52.1},
+                             {"t"=>2, "x"=>4.5},
+                             {"t"=>3, "x"=>4.6, "y"=>52.3},
+                             {"t"=>4, "x"=>4.7, "y"=>52.4}]}
+    rwtf = RWTFile::from_bytes(RWTFile::from_h(data, CONFIG).to_bytes)
+
+    it "returns every field by default" do
+      expect(rwtf.rows).to eq(data["track_points"])
+    end
+    it "projects the requested fields" do
+      expect(rwtf.rows(fields: ["x", "y"])).to eq([{"x"=>4.4, "y"=>52.1},
+                                                   {"x"=>4.5},
+                                                   {"x"=>4.6, "y"=>52.3},
+                                                   {"x"=>4.7, "y"=>52.4}])
+    end
+    it "returns a row range" do
+      expect(rwtf.rows(fields: ["t"], offset: 1, limit: 2)).to eq([{"t"=>2}, {"t"=>3}])
+      expect(rwtf.rows(fields: ["t"], offset: 3, limit: 10)).to eq([{"t"=>4}])
+      expect(rwtf.rows(offset: 10)).to eq([])
+    end
+    it "returns empty rows for a missing section" do
+      expect(rwtf.rows(section: 1)).to eq([])
+    end
+  end
+end
@@ -14,6 +14,7 @@ pub extern "C" fn Init_Tracklib() {
         itself.def("to_h", rwtfile::rwtf_to_hash);
         itself.def("metadata", rwtfile::rwtf_metadata);
         itself.def("simplify_track_points", rwtfile::rwtf_simplify_track_points);
+        itself.def("rows_native", rwtfile::rwtf_rows);
         itself.def("inspect", rwtfile::rwtf_inspect);
         itself.def("to_s", rwtfile::rwtf_inspect);
     });
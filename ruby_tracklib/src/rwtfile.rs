@@ -4,10 +4,11 @@ use rutie::{
     Integer, Module, Object, RString, VM,
 };
 use rutie_serde::{ruby_class, rutie_serde_methods};
+use std::cmp;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io::BufWriter;
-use tracklib::{parse_rwtf, DataField, RWTFMetadata, RWTFile, TrackType};
+use tracklib::{parse_rwtf, Column, DataField, RWTFMetadata, RWTFile, Section, TrackType};
 use super::polyline;
 use super::surface;
 
@@ -189,7 +190,11 @@ fn is_number_and_too_large(v: &AnyObject, field_type: ColumnType) -> bool {
                 match v.try_convert_to::<Integer>() {
                     Ok(i) => i.to_i64().abs() > field_type.max_integer(),
                     Err(_) => match v.try_convert_to::<Float>() {
-                        Ok(f) => f.to_f64().abs() > field_type.max_float(),
+                        // NaN can't be stored either, and compares false with everything
+                        Ok(f) => {
+                            let f = f.to_f64();
+                            f.is_nan() || f.abs() > field_type.max_float()
+                        }
                         Err(_) => false,
                     },
                 }
@@ -262,6 +267,52 @@ fn add_points(
     }
 }
 
+fn any_to_usize(o: AnyObject) -> usize {
+    usize::try_from(any_to_int(o))
+        .map_err(|e| VM::raise(Class::from_existing("ArgumentError"), &format!("{}", e)))
+        .unwrap()
+}
+
+fn column_value(column: &Column, index: usize) -> Option<AnyObject> {
+    match column {
+        Column::Numbers(m) => m.get(&index).map(|v| Integer::new(*v).to_any_object()),
+        Column::LongFloat(m) => m.get(&index).map(|v| Float::new(*v).to_any_object()),
+        Column::ShortFloat(m) => m.get(&index).map(|v| Float::new(*v).to_any_object()),
+        Column::Base64(m) => m
+            .get(&index)
+            .map(|v| RString::new_utf8(&base64::encode(v)).to_any_object()),
+        Column::String(m) => m.get(&index).map(|v| RString::new_utf8(v).to_any_object()),
+        Column::Bool(m) => m.get(&index).map(|v| Boolean::new(*v).to_any_object()),
+        Column::IDs(m) => m.get(&index).map(|v| {
+            v.iter()
+                .map(|id| Integer::from(*id).to_any_object())
+                .collect::<Array>()
+                .to_any_object()
+        }),
+    }
+}
+
+fn section_rows(section: &Section, fields: &[String], offset: usize, limit: Option<usize>) -> Array {
+    let columns = section.columns();
+    let end = match limit {
+        Some(limit) => cmp::min(section.len(), offset.saturating_add(limit)),
+        None => section.len(),
+    };
+
+    let mut rows = Array::new();
+    for index in offset..end {
+        let mut row = Hash::new();
+        for name in fields {
+            if let Some(value) = columns.get(name).and_then(|column| column_value(column, index)) {
+                row.store(RString::new_utf8(name), value);
+            }
+        }
+        rows.push(row);
+    }
+
+    rows
+}
+
 pub struct Inner {
     inner: RWTFile,
 }
@@ -403,6 +454,44 @@ methods!(
         RString::new_utf8(&track_points.simplify_and_encode(mapping, tol, enc_opts))
     }
 
+    fn rwtf_rows(section: Integer, fields: AnyObject, offset: Integer, limit: AnyObject) -> Array {
+        let rwtf = &itself.get_data(&*INNER_WRAPPER).inner;
+        let section = match section.map_err(|e| VM::raise_ex(e)).unwrap().to_i64() {
+            0 => &rwtf.track_points,
+            1 => &rwtf.course_points,
+            n => {
+                VM::raise(
+                    Class::from_existing("ArgumentError"),
+                    &format!("unknown section: {}", n),
+                );
+                unreachable!();
+            }
+        };
+
+        let fields_obj = fields.map_err(|e| VM::raise_ex(e)).unwrap();
+        let field_names: Vec<String> = if fields_obj.is_nil() {
+            section.columns().keys().cloned().collect()
+        } else {
+            fields_obj
+                .try_convert_to::<Array>()
+                .map_err(|e| VM::raise_ex(e))
+                .unwrap()
+                .into_iter()
+                .map(any_to_str)
+                .collect()
+        };
+
+        let offset = any_to_usize(offset.map_err(|e| VM::raise_ex(e)).unwrap().to_any_object());
+        let limit_obj = limit.map_err(|e| VM::raise_ex(e)).unwrap();
+        let limit = if limit_obj.is_nil() {
+            None
+        } else {
+            Some(any_to_usize(limit_obj))
+        };
+
+        section_rows(section, &field_names, offset, limit)
+    }
+
     fn rwtf_inspect() -> RString {
         let rwtf = &itself.get_data(&*INNER_WRAPPER).inner;
 
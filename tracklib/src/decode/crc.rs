@@ -13,4 +13,11 @@ impl<T: PartialEq> CRC<T> {
         }
 
     }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        match self {
+            CRC::Valid(_) => true,
+            CRC::Invalid{..} => false,
+        }
+    }
 }
@@ -0,0 +1,100 @@
+use std::convert::{TryFrom};
+
+use crate::rwtfile::{RWTFTRAILER};
+use crate::section::{SectionType};
+use crate::decode::{FileParts, SectionRange, LayoutError};
+
+// Writes a new file from the original header and metadata table and the given
+// sections, which are copied byte for byte.
+fn write_file<'a, I: Iterator<Item = &'a SectionRange>>(data: &[u8], parts: &FileParts, sections: I) -> Result<Vec<u8>, LayoutError> {
+    let metadata_table = &data[parts.metadata_range.clone()];
+
+    let header_size: u16 = 24;
+    let metadata_table_offset: u16 = header_size;
+    let data_offset = u16::try_from(metadata_table.len()).ok()
+        .and_then(|size| metadata_table_offset.checked_add(size))
+        .ok_or_else(|| LayoutError::new(parts.metadata_range.start, "metadata table is too large"))?;
+
+    let mut out = vec![];
+    parts.header.write(&mut out, metadata_table_offset, data_offset)
+        .map_err(|_| LayoutError::new(0, "couldn't write the file header"))?;
+    out.extend_from_slice(metadata_table);
+    for section in sections {
+        out.extend_from_slice(&data[section.start..section.end]);
+    }
+    out.extend_from_slice(&RWTFTRAILER);
+
+    Ok(out)
+}
+
+// Copies the section of type `section_type` out of `data` into a file of its
+// own, with the same header and metadata. Column data isn't decoded, so this
+// works on files with corrupt or unsupported columns too.
+pub fn extract_section(data: &[u8], section_type: SectionType) -> Result<Vec<u8>, LayoutError> {
+    let parts = FileParts::parse(data)?;
+    let sections = parts.sections(data).collect::<Result<Vec<_>, _>>()?;
+    let section = sections.iter()
+        .find(|section| section.header.section_type == section_type)
+        .ok_or_else(|| LayoutError::new(data.len(), format!("file has no {:?} section", section_type)))?;
+
+    write_file(data, &parts, std::iter::once(section))
+}
+
+// Copies `data` without any of the sections whose type is in `section_types`.
+// Column data isn't decoded, as with extract_section.
+pub fn remove_sections(data: &[u8], section_types: &[SectionType]) -> Result<Vec<u8>, LayoutError> {
+    let parts = FileParts::parse(data)?;
+    let sections = parts.sections(data).collect::<Result<Vec<_>, _>>()?;
+    let kept = sections.iter()
+        .filter(|section| !section_types.contains(&section.header.section_type));
+
+    write_file(data, &parts, kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{TrackType};
+    use crate::decode::{parse_rwtf_strict, write_test_file};
+
+    #[test]
+    fn test_extract_section() {
+        let buf = write_test_file();
+
+        let r = extract_section(&buf, SectionType::TrackPoints);
+        assert!(r.is_ok());
+        let extracted = parse_rwtf_strict(&r.unwrap()).unwrap();
+        assert_eq!(extracted.metadata().track_type(), Some(TrackType::Route(4)));
+        assert_eq!(extracted.track_points.len(), 10);
+        assert_eq!(extracted.track_points.column::<i64>("t").unwrap().last(), Some(Some(&9)));
+        assert_eq!(extracted.course_points.len(), 0);
+
+        let r = extract_section(&buf, SectionType::CoursePoints);
+        assert!(r.is_ok());
+        let extracted = parse_rwtf_strict(&r.unwrap()).unwrap();
+        assert_eq!(extracted.track_points.len(), 0);
+        assert_eq!(extracted.course_points.column::<String>("name").unwrap().next(), Some(Some(&"turn left".to_string())));
+
+        assert_eq!(extract_section(&buf, SectionType::Continuation).err(),
+                   Some(LayoutError::new(buf.len(), "file has no Continuation section")));
+    }
+
+    #[test]
+    fn test_remove_sections() {
+        let buf = write_test_file();
+
+        let r = remove_sections(&buf, &[SectionType::CoursePoints]);
+        assert!(r.is_ok());
+        let removed = r.unwrap();
+        assert_eq!(Some(&removed), extract_section(&buf, SectionType::TrackPoints).ok().as_ref());
+
+        let r = remove_sections(&buf, &[SectionType::TrackPoints, SectionType::CoursePoints]);
+        assert!(r.is_ok());
+        let removed = parse_rwtf_strict(&r.unwrap()).unwrap();
+        assert_eq!(removed.metadata().track_type(), Some(TrackType::Route(4)));
+        assert_eq!(removed.track_points.len(), 0);
+        assert_eq!(removed.course_points.len(), 0);
+
+        assert_eq!(remove_sections(&buf, &[]).ok(), Some(buf));
+    }
+}
@@ -0,0 +1,370 @@
+use std::ops::{Range};
+use nom::*;
+
+use crate::rwtfile::{RWTFTRAILER};
+use crate::section::{SectionType};
+use crate::decode::{ColumnType, FileParts, SectionRange, parse_types_table};
+use crate::decode::varint::{take_signed_leb128, take_unsigned_leb128};
+
+#[derive(Debug)]
+pub struct ColumnLayout {
+    pub name: String,
+    pub type_name: &'static str,
+    pub bytes: Range<usize>,
+}
+
+// The parts of a section which couldn't be found (because the section is
+// corrupt, or because it has no points) are None.
+#[derive(Debug)]
+pub struct SectionLayout {
+    pub section_type: SectionType,
+    pub points: usize,
+    pub header: Range<usize>,
+    pub types_table: Option<Range<usize>>,
+    pub flags: Option<Range<usize>>,
+    pub columns: Vec<ColumnLayout>,
+    pub data_crc: Option<Range<usize>>,
+}
+
+// Where, and why, inspecting a file stopped
+#[derive(Debug, PartialEq)]
+pub struct LayoutError {
+    pub offset: usize,
+    pub reason: String,
+}
+
+impl LayoutError {
+    pub(crate) fn new<S: Into<String>>(offset: usize, reason: S) -> Self {
+        LayoutError{offset, reason: reason.into()}
+    }
+}
+
+// Byte ranges of every part of a file, relative to the start of the file. If
+// the file is corrupt, this holds everything found before the corruption
+// along with an error describing it.
+#[derive(Debug)]
+pub struct FileLayout {
+    pub header: Option<Range<usize>>,
+    pub metadata: Option<Range<usize>>,
+    pub sections: Vec<SectionLayout>,
+    pub trailer: Option<Range<usize>>,
+    pub error: Option<LayoutError>,
+}
+
+impl FileLayout {
+    fn regions(&self) -> Vec<(String, Range<usize>)> {
+        let mut regions = vec![];
+
+        if let Some(header) = &self.header {
+            regions.push(("header".to_string(), header.clone()));
+        }
+        if let Some(metadata) = &self.metadata {
+            regions.push(("metadata".to_string(), metadata.clone()));
+        }
+
+        for (i, section) in self.sections.iter().enumerate() {
+            let prefix = format!("section {} ({:?}, {} points)", i, section.section_type, section.points);
+            regions.push((format!("{} header", prefix), section.header.clone()));
+            if let Some(types_table) = &section.types_table {
+                regions.push((format!("{} types table", prefix), types_table.clone()));
+            }
+            if let Some(flags) = &section.flags {
+                regions.push((format!("{} flags", prefix), flags.clone()));
+            }
+            for column in section.columns.iter() {
+                regions.push((format!("{} column {} ({})", prefix, column.name, column.type_name), column.bytes.clone()));
+            }
+            if let Some(data_crc) = &section.data_crc {
+                regions.push((format!("{} data crc", prefix), data_crc.clone()));
+            }
+        }
+
+        if let Some(trailer) = &self.trailer {
+            regions.push(("trailer".to_string(), trailer.clone()));
+        }
+        regions
+    }
+
+    // Renders `data` as a hexdump, with a label before every part of the
+    // file. If inspecting the file failed, the error is printed after the
+    // last part that was found, followed by the rest of the bytes.
+    pub fn annotated_hexdump(&self, data: &[u8]) -> String {
+        let mut out = String::new();
+        let mut end = 0;
+
+        for (label, range) in self.regions() {
+            end = range.end;
+            dump(&mut out, data, &label, range);
+        }
+
+        if let Some(error) = &self.error {
+            out.push_str(&format!("{:08x}  error: {}\n", error.offset, error.reason));
+            if end < data.len() {
+                dump(&mut out, data, "unparsed", end..data.len());
+            }
+        }
+
+        out
+    }
+}
+
+fn dump(out: &mut String, data: &[u8], label: &str, range: Range<usize>) {
+    out.push_str(&format!("{:08x}  {} [{} bytes]\n", range.start, label, range.len()));
+
+    let bytes = data.get(range.clone()).unwrap_or(&[]);
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex = chunk.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+        out.push_str(&format!("{:08x}    {}\n", range.start + i * 16, hex));
+    }
+}
+
+// Every row of a column is written, present or not, so a column can be
+// stepped over without looking at the flags or decoding any values.
+fn skip_row<'a>(i: &'a [u8], column_type: &ColumnType) -> IResult<&'a [u8], ()> {
+    match column_type {
+        ColumnType::Numbers | ColumnType::LongFloat | ColumnType::ShortFloat => {
+            let (rest, _delta) = take_signed_leb128(i)?;
+            Ok((rest, ()))
+        }
+        ColumnType::Base64 | ColumnType::String => {
+            let (rest, len) = take_unsigned_leb128(i)?;
+            let (rest, _bytes) = take!(rest, len)?;
+            Ok((rest, ()))
+        }
+        ColumnType::Bool => {
+            let (rest, _b) = le_u8(i)?;
+            Ok((rest, ()))
+        }
+        ColumnType::IDs => {
+            let (mut rest, count) = take_unsigned_leb128(i)?;
+            for _ in 0..count {
+                let (new_rest, _id) = take_unsigned_leb128(rest)?;
+                rest = new_rest;
+            }
+            Ok((rest, ()))
+        }
+    }
+}
+
+// Fills in `layout` from the bytes after the section's header
+fn inspect_section(data: &[u8], section: &SectionRange, layout: &mut SectionLayout) -> Result<(), LayoutError> {
+    let header_end = section.header_end;
+    let end = section.end;
+
+    if section.header.points == 0 {
+        // a section without any points is written as just a header
+        if end != header_end {
+            return Err(LayoutError::new(header_end, "section has no points but has data"));
+        }
+        return Ok(());
+    }
+
+    let crc_start = end.checked_sub(4)
+        .filter(|crc_start| *crc_start >= header_end)
+        .ok_or_else(|| LayoutError::new(header_end, "section is too short to hold a data crc"))?;
+    // don't let anything run into the data crc
+    let body = &data[..crc_start];
+
+    let (rest, types_table) = parse_types_table(&body[header_end..])
+        .map_err(|_| LayoutError::new(header_end, "couldn't parse the types table"))?;
+    let types_table_end = body.offset(rest);
+    layout.types_table = Some(header_end..types_table_end);
+
+    let width = types_table.entries.len().div_ceil(8);
+    let flags_end = types_table_end + width * section.header.points as usize;
+    if flags_end > crc_start {
+        return Err(LayoutError::new(types_table_end, "flags column runs into the data crc"));
+    }
+    layout.flags = Some(types_table_end..flags_end);
+
+    let mut rest = &body[flags_end..];
+    for entry in types_table.entries.iter() {
+        let column_start = body.offset(rest);
+        for row in 0..section.header.points {
+            let row_start = body.offset(rest);
+            let (new_rest, _) = skip_row(rest, &entry.column_type)
+                .map_err(|_| LayoutError::new(row_start, format!("couldn't step over row {} of column {}", row, entry.name)))?;
+            rest = new_rest;
+        }
+        layout.columns.push(ColumnLayout{name: entry.name.clone(),
+                                         type_name: entry.column_type.type_name(),
+                                         bytes: column_start..body.offset(rest)});
+    }
+
+    let columns_end = body.offset(rest);
+    if columns_end != crc_start {
+        return Err(LayoutError::new(columns_end, format!("column data ends {} bytes before the data crc", crc_start - columns_end)));
+    }
+    layout.data_crc = Some(crc_start..end);
+
+    Ok(())
+}
+
+fn inspect_file(data: &[u8], layout: &mut FileLayout) -> Result<(), LayoutError> {
+    let parts = FileParts::parse(data)?;
+    layout.header = Some(0..parts.header_len);
+    layout.metadata = Some(parts.metadata_range.clone());
+
+    let mut sections = parts.sections(data);
+    for section in sections.by_ref() {
+        let section = section?;
+        let mut section_layout = SectionLayout{section_type: section.header.section_type,
+                                               points: section.header.points as usize,
+                                               header: section.start..section.header_end,
+                                               types_table: None,
+                                               flags: None,
+                                               columns: vec![],
+                                               data_crc: None};
+        let r = inspect_section(data, &section, &mut section_layout);
+        layout.sections.push(section_layout);
+        r?;
+    }
+
+    layout.trailer = Some(sections.offset..sections.offset + RWTFTRAILER.len());
+    Ok(())
+}
+
+// Finds the byte range of every part of the file, for debugging corrupt
+// files. Column values are stepped over, never decoded.
+pub fn inspect_rwtf(data: &[u8]) -> FileLayout {
+    let mut layout = FileLayout{header: None,
+                                metadata: None,
+                                sections: vec![],
+                                trailer: None,
+                                error: None};
+
+    if let Err(e) = inspect_file(data, &mut layout) {
+        layout.error = Some(e);
+    }
+
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rwtfile::{RWTFile};
+    use crate::section::{Section};
+    use crate::decode::{write_test_file};
+
+    #[test]
+    fn test_inspect_layout() {
+        let buf = write_test_file();
+
+        let layout = inspect_rwtf(&buf);
+        assert_eq!(layout.error, None);
+        assert_eq!(layout.header, Some(0..24));
+        assert_eq!(layout.metadata.as_ref().map(|m| m.start), Some(24));
+        assert_eq!(layout.sections.len(), 2);
+        assert_eq!(layout.trailer, Some(buf.len() - 5..buf.len()));
+
+        // every byte of the file is accounted for, in order
+        let regions = layout.regions();
+        assert_eq!(regions.first().unwrap().1.start, 0);
+        assert_eq!(regions.last().unwrap().1.end, buf.len());
+        for pair in regions.windows(2) {
+            assert_eq!(pair[0].1.end, pair[1].1.start);
+        }
+
+        let track_points = &layout.sections[0];
+        assert_eq!(track_points.section_type, SectionType::TrackPoints);
+        assert_eq!(track_points.points, 10);
+        assert_eq!(track_points.header.len(), 14);
+        assert_eq!(track_points.flags.as_ref().map(|f| f.len()), Some(10));
+        assert_eq!(track_points.data_crc.as_ref().map(|c| c.len()), Some(4));
+        // the 10 single byte deltas
+        assert_eq!(track_points.columns.iter().find(|c| c.name == "t").map(|c| c.bytes.len()), Some(10));
+        // 9 absent rows, plus a count and 3 ids (300 takes two bytes)
+        assert_eq!(track_points.columns.iter().find(|c| c.name == "ids").map(|c| c.bytes.len()), Some(9 + 5));
+
+        let course_points = &layout.sections[1];
+        assert_eq!(course_points.columns.iter().map(|c| c.type_name).collect::<Vec<_>>(), vec!["String", "Bool"]);
+    }
+
+    #[test]
+    fn test_inspect_empty_section() {
+        let mut buf = write_test_file();
+
+        // splice a header-only section in before the trailer
+        let mut empty = vec![];
+        assert!(Section::new(SectionType::CoursePoints).write(&mut empty).is_ok());
+        let trailer = buf.split_off(buf.len() - RWTFTRAILER.len());
+        buf.extend_from_slice(&empty);
+        buf.extend_from_slice(&trailer);
+
+        let layout = inspect_rwtf(&buf);
+        assert_eq!(layout.error, None);
+        assert_eq!(layout.sections.len(), 3);
+        let section = &layout.sections[2];
+        assert_eq!(section.points, 0);
+        assert_eq!(section.header, buf.len() - 19..buf.len() - 5);
+        assert_eq!(section.types_table, None);
+        assert!(section.columns.is_empty());
+        assert_eq!(layout.trailer, Some(buf.len() - 5..buf.len()));
+    }
+
+    #[test]
+    fn test_annotated_hexdump() {
+        let mut f = RWTFile::new();
+        assert!(f.add_track_point(0, "t", 5).is_ok());
+
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
+
+        let layout = inspect_rwtf(&buf);
+        let dump = layout.annotated_hexdump(&buf);
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "00000000  header [24 bytes]");
+        assert!(lines[1].starts_with("00000000    89 52 57 54 46 0a 1a 0a"));
+        assert!(dump.contains("section 0 (TrackPoints, 1 points) column t (Numbers) [1 bytes]"));
+        assert_eq!(lines[lines.len() - 2], format!("{:08x}  trailer [5 bytes]", buf.len() - 5));
+        assert_eq!(lines[lines.len() - 1], format!("{:08x}    ff 46 54 57 52", buf.len() - 5));
+    }
+
+    #[test]
+    fn test_inspect_truncated_file() {
+        let buf = write_test_file();
+        let full = inspect_rwtf(&buf);
+        let course_points_start = full.sections[1].header.start;
+
+        // cut off in the middle of the course points section
+        let truncated = &buf[..buf.len() - 10];
+        let layout = inspect_rwtf(truncated);
+        assert!(layout.header.is_some());
+        assert!(layout.metadata.is_some());
+        assert_eq!(layout.trailer, None);
+        // the track points section is intact, the course points section
+        // doesn't fit
+        assert_eq!(layout.sections.len(), 1);
+        assert_eq!(layout.sections[0].data_crc, full.sections[0].data_crc);
+        assert_eq!(layout.error.as_ref().map(|e| e.offset), Some(course_points_start));
+
+        let dump = layout.annotated_hexdump(truncated);
+        assert!(dump.contains(&format!("{:08x}  error: section size", course_points_start)));
+        let unparsed = truncated.len() - course_points_start;
+        assert!(dump.contains(&format!("{:08x}  unparsed [{} bytes]", course_points_start, unparsed)));
+
+        // cut off in the header
+        let layout = inspect_rwtf(&buf[..10]);
+        assert_eq!(layout.header, None);
+        assert_eq!(layout.error, Some(LayoutError::new(0, "couldn't parse the file header")));
+    }
+
+    #[test]
+    fn test_inspect_corrupt_column() {
+        let mut buf = write_test_file();
+        let full = inspect_rwtf(&buf);
+        let ids = full.sections[0].columns.iter().find(|c| c.name == "ids").unwrap();
+
+        // claim that the 4th row of the ids column has 100 ids
+        let count_offset = ids.bytes.start + 3;
+        assert_eq!(buf[count_offset], 3);
+        buf[count_offset] = 100;
+
+        let layout = inspect_rwtf(&buf);
+        assert_eq!(layout.error.as_ref().map(|e| e.offset), Some(count_offset));
+        assert_eq!(layout.error.as_ref().map(|e| e.reason.as_str()), Some("couldn't step over row 3 of column ids"));
+        assert_eq!(layout.sections.len(), 1);
+        assert!(layout.sections[0].flags.is_some());
+    }
+}
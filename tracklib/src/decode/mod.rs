@@ -1,20 +1,31 @@
 use std::iter::FromIterator;
 use std::time::{UNIX_EPOCH, Duration};
 use std::collections::{BTreeMap};
+use std::convert::{TryFrom};
+use std::ops::{Range};
 use nom::*;
 use ::crc::crc32::{checksum_ieee};
 use ::crc::crc16::{checksum_usb};
 
 mod varint;
 mod crc;
+mod verify;
+mod inspect;
+mod strict;
+mod extract;
 
-use varint::{take_signed_leb128, take_unsigned_leb128};
+use varint::{take_signed_leb128_batch, take_unsigned_leb128};
 use crate::flagscolumn::{FlagsColumn};
 use crate::rwtfile::{RWTFMAGIC, RWTFTRAILER, RWTFHeader, RWTFile};
-use crate::metadata::{RWTFMetadata, TrackType};
+use crate::metadata::{RWTFMetadata, TrackType, TimeZone};
 use crate::section::{Column, Section, SectionType};
 use crate::decode::crc::{CRC};
 
+pub use verify::{verify_rwtf, IntegrityReport, SectionIntegrity};
+pub use inspect::{inspect_rwtf, FileLayout, SectionLayout, ColumnLayout, LayoutError};
+pub use strict::{parse_rwtf_strict};
+pub use extract::{extract_section, remove_sections};
+
 trait Parsable {
     type Return;
 
@@ -60,6 +71,7 @@ impl Parsable for RWTFHeader {
 enum RWTFMetadataEntry {
     TrackType(TrackType),
     CreatedAt(u64),
+    TimeZone(TimeZone),
     Unknown,
 }
 
@@ -84,6 +96,17 @@ fn parse_metadata_table_entry_data(i: &[u8], tag: u8) -> IResult<&[u8], RWTFMeta
                       timestamp: le_u64 >>
                       (RWTFMetadataEntry::CreatedAt(timestamp)))
         }
+        0x02 => {
+            let (rest, size) = le_u16(i)?;
+            let (rest, data) = take!(rest, size)?;
+            // An entry too short to hold the offset is skipped like an unknown one
+            if data.len() < 4 {
+                return Ok((rest, RWTFMetadataEntry::Unknown));
+            }
+            let (name, utc_offset) = le_i32(data)?;
+            let name = if name.is_empty() { None } else { Some(String::from_utf8_lossy(name).into_owned()) };
+            Ok((rest, RWTFMetadataEntry::TimeZone(TimeZone::new(utc_offset, name))))
+        }
         _ => {
             let (rest, size) = le_u16(i)?;
             let (rest, _data) = take!(rest, size)?;
@@ -114,6 +137,7 @@ impl Parsable for RWTFMetadata {
 
         let mut created_at = None;
         let mut track_type = None;
+        let mut time_zone = None;
 
         for entry in entries {
             match entry {
@@ -123,11 +147,17 @@ impl Parsable for RWTFMetadata {
                 RWTFMetadataEntry::CreatedAt(time) => {
                     created_at = UNIX_EPOCH.checked_add(Duration::new(time, 0));
                 },
+                RWTFMetadataEntry::TimeZone(tz) => {
+                    time_zone = Some(tz)
+                },
                 RWTFMetadataEntry::Unknown => {},
             }
         }
 
-        Ok((rest, (RWTFMetadata::new(created_at, track_type),
+        let mut metadata = RWTFMetadata::new(created_at, track_type);
+        metadata.set_time_zone(time_zone);
+
+        Ok((rest, (metadata,
                    CRC::new(crc, checksum_usb(&i[..diff])))))
     }
 }
@@ -200,6 +230,18 @@ impl ColumnType {
             _ => None
         }
     }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            ColumnType::Numbers => "Numbers",
+            ColumnType::LongFloat => "LongFloat",
+            ColumnType::ShortFloat => "ShortFloat",
+            ColumnType::Base64 => "Base64",
+            ColumnType::String => "String",
+            ColumnType::Bool => "Bool",
+            ColumnType::IDs => "IDs",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -224,6 +266,18 @@ fn parse_section_header(i: &[u8]) -> IResult<&[u8], SectionHeader> {
                             crc: CRC::new(crc, checksum_usb(&i[..diff]))}))
 }
 
+impl SectionHeader {
+    // The length of the whole section in bytes, header included.
+    //
+    // N.B. The size written in every section header is two bytes short of
+    // the real section size (it doesn't count the header CRC), so that's
+    // corrected for here. Everything that finds sections by their size
+    // should go through this.
+    fn encoded_len(&self) -> Option<usize> {
+        usize::try_from(self.size).ok()?.checked_add(2)
+    }
+}
+
 #[derive(Debug)]
 struct TypesTableEntry {
     column_type: ColumnType,
@@ -265,10 +319,6 @@ fn parse_types_table(i: &[u8]) -> IResult<&[u8], TypesTable> {
                          crc: CRC::new(crc, checksum_usb(&i[..diff]))}))
 }
 
-fn parse_number_row<'a>(i: &'a [u8]) -> IResult<&'a [u8], i64> {
-    take_signed_leb128(i)
-}
-
 fn parse_bytes_row<'a>(i: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
     do_parse!(i,
               len: take_unsigned_leb128 >>
@@ -296,164 +346,194 @@ fn parse_ids_row<'a>(i: &'a [u8]) -> IResult<&'a [u8], Vec<u64>> {
               (entries))
 }
 
-fn parse_column<'a>(i: &'a [u8], column: &TypesTableEntry, flags: &FlagsColumn) -> IResult<&'a [u8], Column> {
+// Every row of a numeric column holds a delta, including rows where the
+// value isn't present (those are written as 0), so the whole column can be
+// decoded in one batch before the presence flags are applied.
+fn parse_numbers<'a>(i: &'a [u8], column: &TypesTableEntry, flags: &FlagsColumn, dense: bool) -> IResult<&'a [u8], BTreeMap<usize, i64>> {
+    let (remainder, deltas) = take_signed_leb128_batch(i, flags.len())?;
+
+    if dense {
+        // every row is present, no need to consult the flags at all
+        let m = deltas.into_iter()
+            .scan(0, |last, delta| {
+                *last += delta;
+                Some(*last)
+            })
+            .enumerate()
+            .collect();
+        return Ok((remainder, m));
+    }
+
+    let mut last = 0;
+    let m = present_rows(&flags.presence_words(&column.name))
+        .map(|index| {
+            last += deltas[index];
+            (index, last)
+        })
+        .collect();
+
+    Ok((remainder, m))
+}
+
+// Yields the index of every set bit in a column's presence words
+fn present_rows(words: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    words.iter().enumerate().flat_map(|(word_index, word)| {
+        let mut bits = *word;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                None
+            } else {
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(word_index * 64 + bit)
+            }
+        })
+    })
+}
+
+// Parses the present rows of a column whose absent rows are each written as
+// a single placeholder byte. Runs of absent rows are skipped in one step.
+fn parse_present_rows<'a, T, F>(i: &'a [u8], column: &TypesTableEntry, flags: &FlagsColumn, dense: bool, parse_row: F) -> IResult<&'a [u8], BTreeMap<usize, T>>
+where F: Fn(&'a [u8]) -> IResult<&'a [u8], T>
+{
+    // every row takes at least one byte, check that before sizing the
+    // presence words by the row count
+    if flags.len() > i.len() {
+        return Err(Err::Error(Context::Code(i, ErrorKind::Custom(0))));
+    }
+
+    let mut remainder = i;
+
+    if dense {
+        // every row is present, no need to consult the flags at all
+        let mut values = Vec::with_capacity(flags.len());
+        for index in 0..flags.len() {
+            let (rest, v) = parse_row(remainder)?;
+            remainder = rest;
+            values.push((index, v));
+        }
+        return Ok((remainder, values.into_iter().collect()));
+    }
+
+    let mut m = BTreeMap::new();
+    let mut next_index = 0;
+    for index in present_rows(&flags.presence_words(&column.name)) {
+        let (rest, _absent) = take!(remainder, index - next_index)?;
+        let (rest, v) = parse_row(rest)?;
+        remainder = rest;
+        next_index = index + 1;
+        m.insert(index, v);
+    }
+    let (rest, _absent) = take!(remainder, flags.len() - next_index)?;
+
+    Ok((rest, m))
+}
+
+fn parse_column<'a>(i: &'a [u8], column: &TypesTableEntry, flags: &FlagsColumn, dense: bool) -> IResult<&'a [u8], Column> {
     match column.column_type {
         ColumnType::Numbers => {
-            let mut m = BTreeMap::new();
-            let mut remainder = i;
-            let mut last = 0;
-            for index in 0..flags.len() {
-                if flags.is_present(index, &column.name) {
-                    let (rest, delta) = parse_number_row(remainder)?;
-                    remainder = rest;
-                    let v = last + delta;
-                    last = v;
-                    m.insert(index, v);
-                } else {
-                    // skip forward one byte
-                    remainder = &remainder[1..];
-                }
-            }
+            let (remainder, m) = parse_numbers(i, column, flags, dense)?;
 
             Ok((remainder, Column::Numbers(m)))
         }
         ColumnType::LongFloat => {
-            let mut m = BTreeMap::new();
-            let mut remainder = i;
-            let mut last = 0;
-            for index in 0..flags.len() {
-                if flags.is_present(index, &column.name) {
-                    let (rest, delta) = parse_number_row(remainder)?;
-                    remainder = rest;
-                    let v = last + delta;
-                    last = v;
-                    m.insert(index, v as f64 / 10000000.0);
-                } else {
-                    // skip forward one byte
-                    remainder = &remainder[1..];
-                }
-            }
+            let (remainder, m) = parse_numbers(i, column, flags, dense)?;
 
-            Ok((remainder, Column::LongFloat(m)))
+            Ok((remainder, Column::LongFloat(m.into_iter().map(|(index, v)| (index, v as f64 / 10000000.0)).collect())))
         }
         ColumnType::ShortFloat => {
-            let mut m = BTreeMap::new();
-            let mut remainder = i;
-            let mut last = 0;
-            for index in 0..flags.len() {
-                if flags.is_present(index, &column.name) {
-                    let (rest, delta) = parse_number_row(remainder)?;
-                    remainder = rest;
-                    let v = last + delta;
-                    last = v;
-                    m.insert(index, v as f64 / 1000.0);
-                } else {
-                    // skip forward one byte
-                    remainder = &remainder[1..];
-                }
-            }
+            let (remainder, m) = parse_numbers(i, column, flags, dense)?;
 
-            Ok((remainder, Column::ShortFloat(m)))
+            Ok((remainder, Column::ShortFloat(m.into_iter().map(|(index, v)| (index, v as f64 / 1000.0)).collect())))
         }
         ColumnType::Base64 => {
-            let mut m = BTreeMap::new();
-            let mut remainder = i;
-            for index in 0..flags.len() {
-                if flags.is_present(index, &column.name) {
-                    let (rest, bytes) = parse_bytes_row(remainder)?;
-                    remainder = rest;
-                    m.insert(index, bytes.to_vec());
-                } else {
-                    // skip forward one byte
-                    remainder = &remainder[1..];
-                }
-            }
+            let (remainder, m) = parse_present_rows(i, column, flags, dense, |i| {
+                parse_bytes_row(i).map(|(rest, bytes)| (rest, bytes.to_vec()))
+            })?;
 
             Ok((remainder, Column::Base64(m)))
         }
         ColumnType::String => {
-            let mut m = BTreeMap::new();
-            let mut remainder = i;
-            for index in 0..flags.len() {
-                if flags.is_present(index, &column.name) {
-                    let (rest, bytes) = parse_bytes_row(remainder)?;
-                    remainder = rest;
-                    m.insert(index, String::from_utf8_lossy(bytes).into_owned());
-                } else {
-                    // skip forward one byte
-                    remainder = &remainder[1..];
-                }
-            }
+            let (remainder, m) = parse_present_rows(i, column, flags, dense, |i| {
+                parse_bytes_row(i).map(|(rest, bytes)| (rest, String::from_utf8_lossy(bytes).into_owned()))
+            })?;
 
             Ok((remainder, Column::String(m)))
         }
         ColumnType::Bool => {
-            let mut m = BTreeMap::new();
-            let mut remainder = i;
-            for index in 0..flags.len() {
-                if flags.is_present(index, &column.name) {
-                    let (rest, b) = parse_bool_row(remainder)?;
-                    remainder = rest;
-                    m.insert(index, b);
-                } else {
-                    // skip forward one byte
-                    remainder = &remainder[1..];
-                }
-            }
+            let (remainder, m) = parse_present_rows(i, column, flags, dense, parse_bool_row)?;
 
             Ok((remainder, Column::Bool(m)))
         }
         ColumnType::IDs => {
-            let mut m = BTreeMap::new();
-            let mut remainder = i;
-            for index in 0..flags.len() {
-                if flags.is_present(index, &column.name) {
-                    let (rest, b) = parse_ids_row(remainder)?;
-                    remainder = rest;
-                    m.insert(index, b);
-                } else {
-                    // skip forward one byte
-                    remainder = &remainder[1..];
-                }
-            }
+            let (remainder, m) = parse_present_rows(i, column, flags, dense, parse_ids_row)?;
 
             Ok((remainder, Column::IDs(m)))
         }
     }
 }
 
-impl Parsable for Section {
-    type Return = Option<Self>;
+// A decoded section along with the CRCs parse_rwtf_strict checks. Sections
+// without any points have no types table or data, so no CRCs for them.
+struct ParsedSection {
+    header: SectionHeader,
+    types_table_crc: Option<CRC<u16>>,
+    data_crc: Option<CRC<u32>>,
+    section: Section,
+}
 
-    fn parse(i: &[u8]) -> IResult<&[u8], Self::Return> {
-        let (rest, section_header) = alt!(i,
-                                          tag!(&RWTFTRAILER) => { |_| None } |
-                                          parse_section_header => {|header| Some(header)})?;
+fn parse_section(i: &[u8]) -> IResult<&[u8], Option<ParsedSection>> {
+    let (rest, section_header) = alt!(i,
+                                      tag!(&RWTFTRAILER) => { |_| None } |
+                                      parse_section_header => { Some })?;
+
+    if let Some(header) = section_header {
+        if header.points == 0 {
+            // a section without any points is written as just a header
+            let section = Section::new(header.section_type);
+            return Ok((rest, Some(ParsedSection{header,
+                                                types_table_crc: None,
+                                                data_crc: None,
+                                                section})));
+        }
 
-        if let Some(header) = section_header {
-            let (rest, types_table) = parse_types_table(rest)?;
+        let (rest, types_table) = parse_types_table(rest)?;
 
-            let data_column_start = i.offset(rest);
-            let (mut rest, flags) = FlagsColumn::parse_flags_column(&rest, &types_table, header.points)?;
+        let data_column_start = i.offset(rest);
+        let (mut rest, flags) = FlagsColumn::parse_flags_column(rest, &types_table, header.points)?;
+        let dense_fields = flags.dense_fields();
 
-            let mut m = BTreeMap::new();
-            for column in types_table.entries.iter() {
-                let (new_rest, data) = parse_column(&rest, &column, &flags)?;
-                rest = new_rest;
-                m.insert(column.name.clone(), data);
-            }
+        let mut m = BTreeMap::new();
+        for column in types_table.entries.iter() {
+            let dense = dense_fields & flags.field_mask(&column.name) != 0;
+            let (new_rest, data) = parse_column(rest, column, &flags, dense)?;
+            rest = new_rest;
+            m.insert(column.name.clone(), data);
+        }
 
-            let data_column_end = i.offset(rest);
-            let (rest, crc) = le_u32(&rest)?;
-            let _actual_crc = CRC::new(crc, checksum_ieee(&i[data_column_start..data_column_end])); // TODO: use this
+        let data_column_end = i.offset(rest);
+        let (rest, crc) = le_u32(rest)?;
+        let data_crc = CRC::new(crc, checksum_ieee(&i[data_column_start..data_column_end]));
+
+        let section = Section{section_type: header.section_type,
+                              max: flags.max(),
+                              flags,
+                              columns: m};
+        Ok((rest, Some(ParsedSection{header,
+                                     types_table_crc: Some(types_table.crc),
+                                     data_crc: Some(data_crc),
+                                     section})))
+    } else {
+        Ok((rest, None))
+    }
+}
 
-            Ok((rest, Some(Section{section_type: header.section_type,
-                                   max: flags.max(),
-                                   flags: flags,
-                                   columns: m})))
-        } else {
-            Ok((rest, None))
-        }
+impl Parsable for Section {
+    type Return = Option<Self>;
+
+    fn parse(i: &[u8]) -> IResult<&[u8], Self::Return> {
+        let (rest, parsed) = parse_section(i)?;
+        Ok((rest, parsed.map(|parsed| parsed.section)))
     }
 }
 
@@ -465,8 +545,8 @@ impl Parsable for RWTFile {
 
     fn parse(i: &[u8]) -> IResult<&[u8], Self::Return> {
         let (_rest, (header, header_details)) = RWTFHeader::parse(i)?;
+        // CRCs are only checked by parse_rwtf_strict
         let (_rest, (metadata, _metadata_crc)) = RWTFMetadata::parse(&i[header_details.metadata_table_offset as usize..])?;
-        // TODO: use metadata_crc
 
         let mut remainder = &i[header_details.data_offset as usize..];
 
@@ -500,7 +580,381 @@ pub fn parse_rwtf(i: &[u8]) -> IResult<&[u8], RWTFile> {
     RWTFile::parse(i)
 }
 
+//////////////////////////////
+//      Section Ranges      //
+//////////////////////////////
+// The parts of a file in front of its sections
+struct FileParts {
+    header: RWTFHeader,
+    header_crc: CRC<u16>,
+    header_len: usize,
+    metadata: RWTFMetadata,
+    metadata_crc: CRC<u16>,
+    metadata_range: Range<usize>,
+    data_offset: usize,
+}
+
+impl FileParts {
+    fn parse(data: &[u8]) -> Result<Self, LayoutError> {
+        let (rest, (header, header_details)) = RWTFHeader::parse(data)
+            .map_err(|_| LayoutError::new(0, "couldn't parse the file header"))?;
+        let header_len = data.offset(rest);
+
+        let metadata_start = header_details.metadata_table_offset as usize;
+        let (rest, (metadata, metadata_crc)) = data.get(metadata_start..)
+            .and_then(|metadata_table| RWTFMetadata::parse(metadata_table).ok())
+            .ok_or_else(|| LayoutError::new(metadata_start, "couldn't parse the metadata table"))?;
+
+        Ok(FileParts{header,
+                     header_crc: header_details.crc,
+                     header_len,
+                     metadata,
+                     metadata_crc,
+                     metadata_range: metadata_start..data.offset(rest),
+                     data_offset: header_details.data_offset as usize})
+    }
+
+    fn sections<'a>(&self, data: &'a [u8]) -> SectionRanges<'a> {
+        SectionRanges{data, offset: self.data_offset, done: false}
+    }
+}
+
+// Where a section sits in a file, found from its header alone. Offsets are
+// relative to the start of the file.
+#[derive(Debug)]
+struct SectionRange {
+    header: SectionHeader,
+    start: usize,
+    header_end: usize,
+    end: usize,
+}
+
+// Walks the sections of a file using the sizes in their headers, without
+// looking at any column data. Once it's done, `offset` is where the file
+// trailer starts.
+struct SectionRanges<'a> {
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> SectionRanges<'a> {
+    fn next_section(&self) -> Result<Option<SectionRange>, LayoutError> {
+        let start = self.offset;
+        let remainder = self.data.get(start..)
+            .ok_or_else(|| LayoutError::new(start, "section data starts past the end of the file"))?;
+
+        if remainder.starts_with(&RWTFTRAILER) {
+            return Ok(None);
+        }
+
+        let (rest, header) = parse_section_header(remainder)
+            .map_err(|_| LayoutError::new(start, "couldn't parse a section header or the file trailer"))?;
+        let header_end = start + remainder.offset(rest);
+        let end = header.encoded_len()
+            .and_then(|len| start.checked_add(len))
+            .filter(|end| *end >= header_end && *end <= self.data.len())
+            .ok_or_else(|| LayoutError::new(start, format!("section size {} doesn't fit in the file", header.size)))?;
+
+        Ok(Some(SectionRange{header, start, header_end, end}))
+    }
+}
+
+impl<'a> Iterator for SectionRanges<'a> {
+    type Item = Result<SectionRange, LayoutError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_section() {
+            Ok(Some(section)) => {
+                self.offset = section.end;
+                Some(Ok(section))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn write_test_file() -> Vec<u8> {
+    use crate::rwtfile::{DataField};
+
+    let mut f = RWTFile::with_track_type(TrackType::Route(4));
+    for i in 0..10 {
+        assert!(f.add_track_point(i, "t", i as i64).is_ok());
+    }
+    assert!(f.add_track_point(3, "ids", DataField::IDs(vec![1, 2, 300])).is_ok());
+    assert!(f.add_course_point(0, "name", DataField::String("turn left".into())).is_ok());
+    assert!(f.add_course_point(1, "stop", DataField::Bool(true)).is_ok());
+
+    let mut buf = vec![];
+    assert!(f.write(&mut buf).is_ok());
+    buf
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use crate::rwtfile::DataField;
+
+    #[test]
+    fn test_roundtrip_numeric_columns() {
+        let mut f = RWTFile::new();
+        for i in 0..100 {
+            assert!(f.add_track_point(i, "t", (i as i64) * 1000 - 30000).is_ok());
+            if i % 3 != 0 {
+                assert!(f.add_track_point(i, "x", DataField::LongFloat(-122.0 + (i as f64) / 1000.0)).is_ok());
+            }
+            if i % 7 == 0 {
+                assert!(f.add_track_point(i, "d", DataField::ShortFloat((i as f64) * 2.5)).is_ok());
+            }
+        }
+        assert!(f.add_course_point(0, "t", 5).is_ok());
+
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
+
+        let (rest, parsed) = parse_rwtf(&buf).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed.track_points.len(), 100);
+        assert_eq!(parsed.course_points.len(), 1);
+
+        let columns = parsed.track_points.columns();
+        assert_matches!(columns.get("t"), Some(Column::Numbers(m)) => {
+            assert_eq!(m.len(), 100);
+            assert_eq!(m.get(&0), Some(&-30000));
+            assert_eq!(m.get(&99), Some(&69000));
+        });
+        assert_matches!(columns.get("x"), Some(Column::LongFloat(m)) => {
+            assert_eq!(m.len(), 66);
+            assert_eq!(m.get(&0), None);
+            assert!((m[&1] - -121.999).abs() < 1e-7);
+            assert!((m[&98] - -121.902).abs() < 1e-7);
+        });
+        assert_matches!(columns.get("d"), Some(Column::ShortFloat(m)) => {
+            assert_eq!(m.keys().copied().collect::<Vec<_>>(), (0..100).step_by(7).collect::<Vec<_>>());
+            assert_eq!(m.get(&98), Some(&245.0));
+        });
+    }
+
+    #[test]
+    fn test_roundtrip_dense_and_sparse_columns() {
+        let mut f = RWTFile::new();
+        for i in 0..20 {
+            assert!(f.add_track_point(i, "name", DataField::String(format!("p{}", i))).is_ok());
+            assert!(f.add_track_point(i, "ids", DataField::IDs(vec![i as u64, 1 << 40])).is_ok());
+            if i % 2 == 0 {
+                assert!(f.add_track_point(i, "stop", DataField::Bool(i % 4 == 0)).is_ok());
+                assert!(f.add_track_point(i, "ep", DataField::Base64("SGVsbG8=".into())).is_ok());
+            }
+        }
+
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
+
+        let (_, parsed) = parse_rwtf(&buf).unwrap();
+        let columns = parsed.track_points.columns();
+        assert_matches!(columns.get("name"), Some(Column::String(m)) => {
+            assert_eq!(m.len(), 20);
+            assert_eq!(m.get(&19), Some(&"p19".to_string()));
+        });
+        assert_matches!(columns.get("ids"), Some(Column::IDs(m)) => {
+            assert_eq!(m.len(), 20);
+            assert_eq!(m.get(&7), Some(&vec![7, 1 << 40]));
+        });
+        assert_matches!(columns.get("stop"), Some(Column::Bool(m)) => {
+            assert_eq!(m.len(), 10);
+            assert_eq!(m.get(&4), Some(&true));
+            assert_eq!(m.get(&6), Some(&false));
+            assert_eq!(m.get(&5), None);
+        });
+        assert_matches!(columns.get("ep"), Some(Column::Base64(m)) => {
+            assert_eq!(m.len(), 10);
+            assert_eq!(m.get(&18), Some(&b"Hello".to_vec()));
+        });
+    }
+
+    #[test]
+    fn test_roundtrip_time_zone() {
+        let mut f = RWTFile::with_track_type(TrackType::Trip(7));
+        assert!(f.add_track_point(0, "t", 1_600_000_000).is_ok());
+        f.set_time_zone(Some(TimeZone::new(-25200, Some("America/Los_Angeles".to_string()))));
+
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
 
+        let (_, parsed) = parse_rwtf(&buf).unwrap();
+        assert_eq!(parsed.metadata().track_type(), Some(TrackType::Trip(7)));
+        let tz = parsed.metadata().time_zone().unwrap();
+        assert_eq!(tz.utc_offset(), -25200);
+        assert_eq!(tz.name(), Some("America/Los_Angeles"));
+
+        let local_times = tz.local_times(parsed.track_points.column::<i64>("t").unwrap()).collect::<Vec<_>>();
+        assert_eq!(local_times, vec![Some(1_599_974_800)]);
+
+        f.set_time_zone(Some(TimeZone::new(0, None)));
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
+
+        let (_, parsed) = parse_rwtf(&buf).unwrap();
+        assert_eq!(parsed.metadata().time_zone(), Some(&TimeZone::new(0, None)));
+    }
+
+    #[test]
+    fn test_parse_malformed_time_zone() {
+        let buf = &[0x03, // entry count
+                    0x02, // time zone entry too short to hold an offset
+                    0x02,
+                    0x00,
+                    0xAA,
+                    0xBB,
+                    0x02, // time zone entry with an invalid utf-8 name
+                    0x06,
+                    0x00,
+                    0x10, // utc offset = 3600
+                    0x0E,
+                    0x00,
+                    0x00,
+                    0xFF, // invalid utf-8
+                    0x58, // X
+                    0x01, // created_at entry
+                    0x08,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00, // crc
+                    0x00];
+        let (rest, (metadata, _crc)) = RWTFMetadata::parse(buf).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(metadata.time_zone(), Some(&TimeZone::new(3600, Some("\u{FFFD}X".to_string()))));
+        assert_eq!(metadata.created_at(), Some(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_section_ranges() {
+        let buf = write_test_file();
+        let parts = FileParts::parse(&buf).unwrap();
+        assert_eq!(parts.header_len, 24);
+        assert_eq!(parts.metadata_range.start, 24);
+        assert_eq!(parts.metadata_range.end, parts.data_offset);
+
+        let mut sections = parts.sections(&buf);
+        let track_points = sections.next().unwrap().unwrap();
+        let course_points = sections.next().unwrap().unwrap();
+        assert!(sections.next().is_none());
+        assert_eq!(track_points.header.section_type, SectionType::TrackPoints);
+        assert_eq!(track_points.start, parts.data_offset);
+        assert_eq!(track_points.header_end, track_points.start + 14);
+        assert_eq!(track_points.end, course_points.start);
+        assert_eq!(course_points.header.section_type, SectionType::CoursePoints);
+        assert_eq!(course_points.end, sections.offset);
+        assert_eq!(sections.offset, buf.len() - RWTFTRAILER.len());
+    }
+
+    #[test]
+    fn test_section_ranges_truncated() {
+        let buf = write_test_file();
+        let parts = FileParts::parse(&buf).unwrap();
+        let course_points_start = parts.sections(&buf).nth(1).unwrap().unwrap().start;
+
+        // cut off in the middle of the course points section
+        let truncated = &buf[..buf.len() - 10];
+        let mut sections = parts.sections(truncated);
+        assert!(sections.next().unwrap().is_ok());
+        assert_matches!(sections.next(), Some(Err(LayoutError{offset, ..})) if offset == course_points_start);
+        assert!(sections.next().is_none());
+
+        // cut off in the header
+        assert_eq!(FileParts::parse(&buf[..10]).err(), Some(LayoutError::new(0, "couldn't parse the file header")));
+    }
+
+    #[test]
+    fn test_parse_empty_section() {
+        let mut f = RWTFile::new();
+        assert!(f.add_track_point(0, "t", 1).is_ok());
+
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
+
+        // splice a header-only course points section in before the trailer
+        let mut empty = vec![];
+        assert!(Section::new(SectionType::CoursePoints).write(&mut empty).is_ok());
+        assert_eq!(empty.len(), 14);
+        let trailer = buf.split_off(buf.len() - RWTFTRAILER.len());
+        buf.extend_from_slice(&empty);
+        buf.extend_from_slice(&trailer);
+
+        let (rest, parsed) = parse_rwtf(&buf).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed.track_points.len(), 1);
+        assert_eq!(parsed.course_points.len(), 0);
+    }
+
+    #[test]
+    fn test_parse_hostile_point_count() {
+        let mut f = RWTFile::new();
+        for i in 0..10 {
+            assert!(f.add_track_point(i, "t", i as i64).is_ok());
+        }
+
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
+        let (_, (_, header_details)) = RWTFHeader::parse(&buf).unwrap();
+        let points_offset = header_details.data_offset as usize + 1;
+
+        // a section which claims to have no points but still has data
+        let mut hostile = buf.clone();
+        hostile[points_offset..points_offset + 3].copy_from_slice(&[0x00, 0x00, 0x00]);
+        assert!(parse_rwtf(&hostile).is_err());
+
+        // a section which claims to have far more points than there is data
+        let mut hostile = buf.clone();
+        hostile[points_offset..points_offset + 3].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+        assert!(parse_rwtf(&hostile).is_err());
+    }
+
+    #[test]
+    fn test_parse_present_rows_dense_matches_sparse() {
+        let mut flags = FlagsColumn::new();
+        for index in 0..5 {
+            flags.set(index, "stop");
+        }
+        assert_ne!(flags.dense_fields() & flags.field_mask("stop"), 0);
+        let column = TypesTableEntry{column_type: ColumnType::Bool, name: "stop".to_string()};
+        let buf = [0x01, 0x00, 0x00, 0x01, 0x01, 0xAA];
+
+        let (dense_rest, dense) = parse_present_rows(&buf, &column, &flags, true, parse_bool_row).unwrap();
+        let (sparse_rest, sparse) = parse_present_rows(&buf, &column, &flags, false, parse_bool_row).unwrap();
+        assert_eq!(dense, sparse);
+        assert_eq!(dense_rest, &[0xAA]);
+        assert_eq!(sparse_rest, &[0xAA]);
+        assert_eq!(dense.values().copied().collect::<Vec<_>>(), vec![true, false, false, true, true]);
+    }
+
+    #[test]
+    fn test_parse_present_rows_hostile_row_count() {
+        let mut flags = FlagsColumn::new();
+        flags.set(1 << 40, "name");
+        let column = TypesTableEntry{column_type: ColumnType::String, name: "name".to_string()};
+
+        assert!(parse_present_rows(&[0x00, 0x00], &column, &flags, false, parse_bool_row).is_err());
+        assert!(parse_present_rows(&[0x00, 0x00], &column, &flags, true, parse_bool_row).is_err());
+    }
 }
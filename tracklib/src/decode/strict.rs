@@ -0,0 +1,148 @@
+use crate::rwtfile::{RWTFTRAILER, RWTFile};
+use crate::section::{Section, SectionType};
+use crate::decode::{FileParts, ParsedSection, SectionRange, LayoutError, parse_section};
+
+// Decodes one section, which has to fill exactly the bytes its header says
+// it takes up.
+fn parse_section_strict(data: &[u8], section: &SectionRange) -> Result<Section, LayoutError> {
+    let start = section.start;
+    let bytes = &data[start..section.end];
+    let (rest, parsed) = parse_section(bytes)
+        .map_err(|_| LayoutError::new(start, "couldn't decode the section within the size in its header"))?;
+    let ParsedSection{header, types_table_crc, data_crc, section} = parsed
+        .ok_or_else(|| LayoutError::new(start, "couldn't parse the section header"))?;
+
+    if !header.crc.is_valid() {
+        return Err(LayoutError::new(start, "section header crc doesn't match"));
+    }
+    if types_table_crc.is_some_and(|crc| !crc.is_valid()) {
+        return Err(LayoutError::new(start, "types table crc doesn't match"));
+    }
+    if !rest.is_empty() {
+        return Err(LayoutError::new(start, format!("section data ends {} bytes before the size in its header", rest.len())));
+    }
+    if data_crc.is_some_and(|crc| !crc.is_valid()) {
+        return Err(LayoutError::new(start, "section data crc doesn't match"));
+    }
+
+    Ok(section)
+}
+
+// Like parse_rwtf, but fails on the first broken invariant instead of
+// decoding whatever it can: a CRC that doesn't match, a section whose size
+// disagrees with its header, a section type that can't be read, or bytes
+// after the file trailer. Offsets in the error are relative to the start of
+// the file and point at the part that failed.
+pub fn parse_rwtf_strict(data: &[u8]) -> Result<RWTFile, LayoutError> {
+    let parts = FileParts::parse(data)?;
+    if !parts.header_crc.is_valid() {
+        return Err(LayoutError::new(0, "file header crc doesn't match"));
+    }
+    if !parts.metadata_crc.is_valid() {
+        return Err(LayoutError::new(parts.metadata_range.start, "metadata table crc doesn't match"));
+    }
+
+    let mut track_points = None;
+    let mut course_points = None;
+
+    let mut sections = parts.sections(data);
+    for section in sections.by_ref() {
+        let section = section?;
+        match section.header.section_type {
+            SectionType::TrackPoints => track_points = Some(parse_section_strict(data, &section)?),
+            SectionType::CoursePoints => course_points = Some(parse_section_strict(data, &section)?),
+            SectionType::Continuation => return Err(LayoutError::new(section.start, "continuation sections aren't supported")),
+        }
+    }
+
+    let trailer_end = sections.offset + RWTFTRAILER.len();
+    if data.len() > trailer_end {
+        return Err(LayoutError::new(trailer_end, format!("{} bytes after the file trailer", data.len() - trailer_end)));
+    }
+
+    Ok(RWTFile{header: parts.header,
+               metadata: parts.metadata,
+               track_points: track_points.unwrap_or(Section::new(SectionType::TrackPoints)),
+               course_points: course_points.unwrap_or(Section::new(SectionType::CoursePoints))})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use ::crc::crc16::{checksum_usb};
+    use crate::decode::{parse_rwtf, write_test_file};
+
+    fn data_offset(buf: &[u8]) -> usize {
+        u16::from_le_bytes([buf[18], buf[19]]) as usize
+    }
+
+    #[test]
+    fn test_parse_strict_valid_file() {
+        let buf = write_test_file();
+
+        let r = parse_rwtf_strict(&buf);
+        assert!(r.is_ok());
+        let f = r.unwrap();
+        let (_, expected) = parse_rwtf(&buf).unwrap();
+        assert_eq!(f.track_points.len(), expected.track_points.len());
+        assert_eq!(f.course_points.len(), expected.course_points.len());
+        assert_eq!(f.course_points.column::<String>("name").unwrap().next(), Some(Some(&"turn left".to_string())));
+    }
+
+    #[test]
+    fn test_parse_strict_corrupt_data() {
+        let mut buf = write_test_file();
+        // flip a bit in the last byte of the course points data, just
+        // before its 4 byte CRC and the 5 byte trailer
+        let index = buf.len() - 10;
+        buf[index] ^= 0x01;
+
+        assert!(parse_rwtf(&buf).is_ok());
+        let e = parse_rwtf_strict(&buf).err().unwrap();
+        assert_eq!(e.reason, "section data crc doesn't match");
+        assert!(e.offset > data_offset(&buf));
+    }
+
+    #[test]
+    fn test_parse_strict_corrupt_header() {
+        let mut buf = write_test_file();
+        // the file version byte
+        buf[8] = 0x05;
+
+        assert!(parse_rwtf(&buf).is_ok());
+        assert_eq!(parse_rwtf_strict(&buf).err(), Some(LayoutError::new(0, "file header crc doesn't match")));
+    }
+
+    #[test]
+    fn test_parse_strict_section_size() {
+        let mut buf = write_test_file();
+        // grow the size in the track points section header and fix up its
+        // crc, so only the size is wrong
+        let start = data_offset(&buf);
+        let size = u64::from_le_bytes(<[u8; 8]>::try_from(&buf[start + 4..start + 12]).unwrap());
+        buf[start + 4..start + 12].copy_from_slice(&(size + 1).to_le_bytes());
+        let crc = checksum_usb(&buf[start..start + 12]);
+        buf[start + 12..start + 14].copy_from_slice(&crc.to_le_bytes());
+
+        assert!(parse_rwtf(&buf).is_ok());
+        assert_eq!(parse_rwtf_strict(&buf).err(), Some(LayoutError::new(start, "section data ends 1 bytes before the size in its header")));
+
+        // and shrink it, so decoding runs past the end of the section
+        buf[start + 4..start + 12].copy_from_slice(&(size - 1).to_le_bytes());
+        let crc = checksum_usb(&buf[start..start + 12]);
+        buf[start + 12..start + 14].copy_from_slice(&crc.to_le_bytes());
+
+        assert_eq!(parse_rwtf_strict(&buf).err(), Some(LayoutError::new(start, "couldn't decode the section within the size in its header")));
+    }
+
+    #[test]
+    fn test_parse_strict_trailing_bytes() {
+        let mut buf = write_test_file();
+        let end = buf.len();
+        buf.extend_from_slice(&[0x00, 0x01]);
+
+        assert!(parse_rwtf(&buf).is_ok());
+        assert_eq!(parse_rwtf_strict(&buf).err(), Some(LayoutError::new(end, "2 bytes after the file trailer")));
+    }
+}
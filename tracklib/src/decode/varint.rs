@@ -1,3 +1,4 @@
+use std::cmp;
 use nom::{Context, IResult, Err, ErrorKind, Needed, take};
 
 pub const CONTINUATION_BIT: u8 = 1 << 7;
@@ -73,6 +74,95 @@ pub(crate) fn take_signed_leb128(i: &[u8]) -> IResult<&[u8], i64> {
     return Ok((remainder, result));
 }
 
+// Returns the number of leading bytes in `i` which don't have the
+// continuation bit set, i.e. the number of complete one-byte values.
+#[cfg(target_arch = "x86_64")]
+fn single_byte_run(i: &[u8]) -> usize {
+    use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_movemask_epi8};
+
+    let mut run = 0;
+    for chunk in i.chunks_exact(16) {
+        // SSE2 is part of the x86_64 baseline, and movemask collects exactly
+        // the high (continuation) bit of every byte.
+        let mask = unsafe { _mm_movemask_epi8(_mm_loadu_si128(chunk.as_ptr() as *const __m128i)) };
+        if mask != 0 {
+            return run + mask.trailing_zeros() as usize;
+        }
+        run += 16;
+    }
+
+    run + single_byte_run_scalar(&i[run..])
+}
+
+#[cfg(target_arch = "aarch64")]
+fn single_byte_run(i: &[u8]) -> usize {
+    use std::arch::aarch64::{vld1q_u8, vmaxvq_u8};
+
+    let mut run = 0;
+    for chunk in i.chunks_exact(16) {
+        // NEON is part of the aarch64 baseline
+        if unsafe { vmaxvq_u8(vld1q_u8(chunk.as_ptr())) } & CONTINUATION_BIT != 0 {
+            return run + single_byte_run_scalar(chunk);
+        }
+        run += 16;
+    }
+
+    run + single_byte_run_scalar(&i[run..])
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn single_byte_run(i: &[u8]) -> usize {
+    const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+    let mut run = 0;
+    for chunk in i.chunks_exact(8) {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        let mask = u64::from_le_bytes(word) & HIGH_BITS;
+        if mask != 0 {
+            return run + (mask.trailing_zeros() / 8) as usize;
+        }
+        run += 8;
+    }
+
+    run + single_byte_run_scalar(&i[run..])
+}
+
+fn single_byte_run_scalar(i: &[u8]) -> usize {
+    i.iter().take_while(|byte| *byte & CONTINUATION_BIT == 0).count()
+}
+
+fn sign_extend_single_byte(byte: u8) -> i64 {
+    // move the sign bit (bit 6) up to bit 63 and shift back down
+    (i64::from(byte) << 57) >> 57
+}
+
+// Decodes `count` consecutive signed LEB128 values. Runs of one-byte values,
+// which dominate delta-encoded columns, are found with a vectorized scan and
+// decoded without going through the general-purpose parser.
+pub(crate) fn take_signed_leb128_batch(i: &[u8], count: usize) -> IResult<&[u8], Vec<i64>> {
+    // every value takes at least one byte, so a count larger than the input
+    // can't be trusted to size the allocation
+    let mut values = Vec::with_capacity(cmp::min(count, i.len()));
+    let mut remainder = i;
+
+    while values.len() < count {
+        let wanted = cmp::min(remainder.len(), count - values.len());
+        let run = single_byte_run(&remainder[..wanted]);
+
+        if run > 0 {
+            values.extend(remainder[..run].iter().map(|byte| sign_extend_single_byte(*byte)));
+            remainder = &remainder[run..];
+        } else {
+            let (rest, value) = take_signed_leb128(remainder)?;
+            values.push(value);
+            remainder = rest;
+        }
+    }
+
+    Ok((remainder, values))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,4 +214,48 @@ mod tests {
         unsigned_helper(std::u32::MAX as u64);
         unsigned_helper(std::u64::MAX as u64);
     }
+
+    #[test]
+    fn test_signed_batch_matches_single_values() {
+        let values = (-300..300)
+            .chain(vec![i32::MIN as i64, i32::MAX as i64, i64::MIN, i64::MAX])
+            .chain(std::iter::repeat_n(-1, 40))
+            .chain(std::iter::repeat_n(63, 17))
+            .collect::<Vec<i64>>();
+
+        let mut buf = vec![];
+        for v in values.iter() {
+            assert!(leb128::write::signed(&mut buf, *v).is_ok());
+        }
+        buf.extend_from_slice(&[0xAA, 0xBB]);
+
+        let r = take_signed_leb128_batch(&buf, values.len());
+        assert!(r.is_ok());
+        let (rest, decoded) = r.unwrap();
+        assert_eq!(rest, &[0xAA, 0xBB]);
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn test_signed_batch_stops_at_count() {
+        let buf = [0x01, 0x7F, 0x02, 0x03];
+        let (rest, decoded) = take_signed_leb128_batch(&buf, 2).unwrap();
+        assert_eq!(rest, &[0x02, 0x03]);
+        assert_eq!(decoded, vec![1, -1]);
+
+        let (rest, decoded) = take_signed_leb128_batch(&buf, 0).unwrap();
+        assert_eq!(rest, &buf);
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    fn test_signed_batch_incomplete() {
+        assert!(take_signed_leb128_batch(&[0x01, 0x02], 3).is_err());
+        assert!(take_signed_leb128_batch(&[0x01, 0x80], 2).is_err());
+    }
+
+    #[test]
+    fn test_signed_batch_hostile_count() {
+        assert!(take_signed_leb128_batch(&[0x01, 0x02], usize::MAX).is_err());
+    }
 }
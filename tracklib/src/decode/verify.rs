@@ -0,0 +1,121 @@
+use nom::*;
+use ::crc::crc32::{checksum_ieee};
+
+use crate::section::{SectionType};
+use crate::decode::{FileParts, SectionRange, LayoutError, parse_types_table};
+use crate::decode::crc::{CRC};
+
+#[derive(Debug)]
+pub struct SectionIntegrity {
+    pub section_type: SectionType,
+    pub header_crc: bool,
+    pub types_table_crc: bool,
+    pub data_crc: bool,
+}
+
+impl SectionIntegrity {
+    pub fn is_valid(&self) -> bool {
+        self.header_crc && self.types_table_crc && self.data_crc
+    }
+}
+
+#[derive(Debug)]
+pub struct IntegrityReport {
+    pub header_crc: bool,
+    pub metadata_crc: bool,
+    pub sections: Vec<SectionIntegrity>,
+}
+
+impl IntegrityReport {
+    pub fn is_valid(&self) -> bool {
+        self.header_crc && self.metadata_crc && self.sections.iter().all(SectionIntegrity::is_valid)
+    }
+}
+
+fn verify_section(data: &[u8], section: &SectionRange) -> Result<SectionIntegrity, LayoutError> {
+    let body = &data[section.header_end..section.end];
+
+    if body.is_empty() {
+        // a section without any points has no types table or data
+        return Ok(SectionIntegrity{section_type: section.header.section_type,
+                                   header_crc: section.header.crc.is_valid(),
+                                   types_table_crc: true,
+                                   data_crc: true});
+    }
+
+    let (rest, types_table) = parse_types_table(body)
+        .map_err(|_| LayoutError::new(section.header_end, "couldn't parse the types table"))?;
+    let crc_start = rest.len().checked_sub(4)
+        .ok_or_else(|| LayoutError::new(section.header_end, "section is too short to hold a data crc"))?;
+    let (columns, crc_bytes) = rest.split_at(crc_start);
+    let (_, crc) = le_u32(crc_bytes)
+        .map_err(|_| LayoutError::new(section.end - 4, "couldn't read the data crc"))?;
+
+    Ok(SectionIntegrity{section_type: section.header.section_type,
+                        header_crc: section.header.crc.is_valid(),
+                        types_table_crc: types_table.crc.is_valid(),
+                        data_crc: CRC::new(crc, checksum_ieee(columns)).is_valid()})
+}
+
+// Checks every CRC in the file without decoding any column data
+pub fn verify_rwtf(data: &[u8]) -> Result<IntegrityReport, LayoutError> {
+    let parts = FileParts::parse(data)?;
+
+    let mut sections = vec![];
+    for section in parts.sections(data) {
+        sections.push(verify_section(data, &section?)?);
+    }
+
+    Ok(IntegrityReport{header_crc: parts.header_crc.is_valid(),
+                       metadata_crc: parts.metadata_crc.is_valid(),
+                       sections})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::{write_test_file};
+
+    #[test]
+    fn test_verify_valid_file() {
+        let buf = write_test_file();
+
+        let r = verify_rwtf(&buf);
+        assert!(r.is_ok());
+        let report = r.unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.sections.len(), 2);
+        assert_eq!(report.sections[0].section_type, SectionType::TrackPoints);
+        assert_eq!(report.sections[1].section_type, SectionType::CoursePoints);
+    }
+
+    #[test]
+    fn test_verify_corrupt_data() {
+        let mut buf = write_test_file();
+        // flip a bit in the last byte of the course points data, just
+        // before its 4 byte CRC and the 5 byte trailer
+        let index = buf.len() - 10;
+        buf[index] ^= 0x01;
+
+        let report = verify_rwtf(&buf).unwrap();
+        assert!(!report.is_valid());
+        assert!(report.header_crc);
+        assert!(report.metadata_crc);
+        assert!(report.sections[0].is_valid());
+        assert!(report.sections[1].header_crc);
+        assert!(report.sections[1].types_table_crc);
+        assert!(!report.sections[1].data_crc);
+    }
+
+    #[test]
+    fn test_verify_corrupt_header() {
+        let mut buf = write_test_file();
+        // the file version byte
+        buf[8] = 0x05;
+
+        let report = verify_rwtf(&buf).unwrap();
+        assert!(!report.is_valid());
+        assert!(!report.header_crc);
+        assert!(report.sections.iter().all(SectionIntegrity::is_valid));
+    }
+}
@@ -0,0 +1,133 @@
+use snafu::{Snafu, ResultExt};
+use crate::rwtfile::{RWTFile};
+use crate::section::{Section, SectionType, Error as SectionError};
+use crate::simplify::{haversine_distance};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Couldn't redact {:?} section: {}", section_type, source))]
+    RedactSection{section_type: SectionType, source: SectionError},
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+// A privacy zone: every point within `radius` meters of the center is removed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Circle {
+    pub longitude: f64,
+    pub latitude: f64,
+    pub radius: f64,
+}
+
+impl Circle {
+    pub fn new(longitude: f64, latitude: f64, radius: f64) -> Self {
+        Circle{longitude, latitude, radius}
+    }
+
+    fn contains(&self, longitude: f64, latitude: f64) -> bool {
+        haversine_distance(self.longitude, self.latitude, longitude, latitude) <= self.radius
+    }
+}
+
+fn redact_section(section: &Section, zones: &[Circle]) -> Result<Section, SectionError> {
+    // A section without coordinates has nothing that can be located, but
+    // coordinates stored as the wrong type are an error rather than being
+    // passed through unredacted
+    let longitudes = match section.longitude() {
+        Ok(longitudes) => longitudes.collect::<Vec<_>>(),
+        Err(SectionError::MissingColumn{..}) => return Ok(section.clone()),
+        Err(e) => return Err(e),
+    };
+    let latitudes = match section.latitude() {
+        Ok(latitudes) => latitudes.collect::<Vec<_>>(),
+        Err(SectionError::MissingColumn{..}) => return Ok(section.clone()),
+        Err(e) => return Err(e),
+    };
+
+    section.retain_points(|index| match (longitudes[index], latitudes[index]) {
+        (Some(&longitude), Some(&latitude)) => !zones.iter().any(|zone| zone.contains(longitude, latitude)),
+        _ => true,
+    })
+}
+
+// Returns a copy of `file` without the track and course points that fall in
+// any of `zones`. Points are located by the `fields::LONGITUDE` and
+// `fields::LATITUDE` columns, and points missing either are kept. The points
+// that are left are renumbered so there are no gaps. v1 files store no
+// derived stats, so the header and metadata are copied unchanged.
+pub fn redact(file: &RWTFile, zones: &[Circle]) -> Result<RWTFile> {
+    let track_points = redact_section(&file.track_points, zones)
+        .context(RedactSection{section_type: SectionType::TrackPoints})?;
+    let course_points = redact_section(&file.course_points, zones)
+        .context(RedactSection{section_type: SectionType::CoursePoints})?;
+
+    Ok(RWTFile{header: file.header.clone(),
+               metadata: file.metadata.clone(),
+               track_points,
+               course_points})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use crate::rwtfile::{DataField};
+    use crate::metadata::{TrackType};
+    use crate::decode::{parse_rwtf};
+    use crate::fields;
+
+    #[test]
+    fn test_circle_contains() {
+        // roughly 111m per 0.001 degrees of latitude
+        let zone = Circle::new(-122.5, 45.5, 100.0);
+        assert!(zone.contains(-122.5, 45.5));
+        assert!(zone.contains(-122.5, 45.5008));
+        assert!(!zone.contains(-122.5, 45.501));
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut f = RWTFile::with_track_type(TrackType::Trip(3));
+        for i in 0..5 {
+            assert!(f.add_track_point(i, fields::LONGITUDE, DataField::LongFloat(-122.5)).is_ok());
+            assert!(f.add_track_point(i, fields::LATITUDE, DataField::LongFloat(45.5 + i as f64 * 0.01)).is_ok());
+            assert!(f.add_track_point(i, "t", i as i64).is_ok());
+        }
+        // no coordinates, so it's kept
+        assert!(f.add_track_point(5, "t", 5).is_ok());
+        // only found on redacted points, so the column is dropped
+        assert!(f.add_track_point(1, "hr", 150).is_ok());
+        assert!(f.add_course_point(0, "n", DataField::String("no coordinates".to_string())).is_ok());
+
+        // covers the points at indexes 1 and 2
+        let zones = [Circle::new(-122.5, 45.515, 1000.0)];
+        let r = redact(&f, &zones);
+        assert!(r.is_ok());
+        let redacted = r.unwrap();
+
+        assert_eq!(redacted.metadata().track_type(), Some(TrackType::Trip(3)));
+        assert_eq!(redacted.track_points.len(), 4);
+        assert_eq!(redacted.track_points.column::<i64>("t").unwrap().collect::<Vec<_>>(),
+                   vec![Some(&0), Some(&3), Some(&4), Some(&5)]);
+        assert_eq!(redacted.track_points.latitude().unwrap().collect::<Vec<_>>(),
+                   vec![Some(&45.5), Some(&45.53), Some(&45.54), None]);
+        assert!(!redacted.track_points.columns().contains_key("hr"));
+        assert_eq!(redacted.course_points.len(), 1);
+
+        // and it's still a valid file
+        let mut buf = vec![];
+        assert!(redacted.write(&mut buf).is_ok());
+        let (_, parsed) = parse_rwtf(&buf).unwrap();
+        assert_eq!(parsed.track_points.column::<i64>("t").unwrap().count(), 4);
+    }
+
+    #[test]
+    fn test_redact_wrong_coordinate_type() {
+        let mut f = RWTFile::new();
+        assert!(f.add_track_point(0, fields::LONGITUDE, DataField::ShortFloat(-122.5)).is_ok());
+        assert!(f.add_track_point(0, fields::LATITUDE, DataField::LongFloat(45.5)).is_ok());
+
+        assert_matches!(redact(&f, &[Circle::new(-122.5, 45.5, 10.0)]),
+                        Err(Error::RedactSection{section_type: SectionType::TrackPoints, ..}));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::section::{Section, Error as SectionError};
+
+// Canonical names of the fields tracklib itself understands. v1 files don't
+// record what a field means, so writers and readers have to agree on names.
+
+// LongFloat, degrees
+pub const LONGITUDE: &str = "x";
+// LongFloat, degrees
+pub const LATITUDE: &str = "y";
+// LongFloat, meters
+pub const ELEVATION: &str = "e";
+// Numbers, unix seconds
+pub const TIME: &str = "t";
+// Numbers, a surface type id (see `SurfaceMapping`)
+pub const SURFACE_TYPE: &str = "S";
+// Numbers, a road class id (see `SurfaceMapping`)
+pub const ROAD_CLASS: &str = "R";
+
+// Plausible ranges for the fields above. Values outside of these are almost
+// certainly corrupt sensor data.
+const RANGES: &[(&str, f64, f64)] = &[(LONGITUDE, -180.0, 180.0),
+                                      (LATITUDE, -90.0, 90.0),
+                                      (ELEVATION, -500.0, 9000.0)];
+
+#[derive(Debug, PartialEq)]
+pub enum FieldViolation {
+    OutOfRange{field: &'static str, index: usize, value: f64, min: f64, max: f64},
+    WrongType{field: &'static str, expected: &'static str, got: &'static str},
+}
+
+// Checks every known field in `section` against its plausible range. Fields
+// that are absent are skipped, fields stored as another type are reported.
+pub fn validate_ranges(section: &Section) -> Vec<FieldViolation> {
+    let mut violations = vec![];
+
+    for &(field, min, max) in RANGES {
+        match section.long_float_column(field) {
+            Ok(values) => {
+                for (index, value) in values.enumerate() {
+                    if let Some(&value) = value {
+                        if !(min..=max).contains(&value) {
+                            violations.push(FieldViolation::OutOfRange{field, index, value, min, max});
+                        }
+                    }
+                }
+            }
+            Err(SectionError::ColumnTypeMismatch{expected, ..}) => {
+                if let Some(column) = section.columns().get(field) {
+                    violations.push(FieldViolation::WrongType{field, expected, got: column.type_name()});
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::section::{SectionType};
+
+    #[test]
+    fn test_validate_ranges() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_long_float(0, LATITUDE, 45.5).is_ok());
+        assert!(s.add_long_float(1, LATITUDE, 91.0).is_ok());
+        assert!(s.add_long_float(1, LONGITUDE, -122.5).is_ok());
+        assert!(s.add_long_float(2, LONGITUDE, 200.0).is_ok());
+        assert!(s.add_long_float(3, ELEVATION, 12000.0).is_ok());
+
+        assert_eq!(validate_ranges(&s),
+                   vec![FieldViolation::OutOfRange{field: LONGITUDE, index: 2, value: 200.0, min: -180.0, max: 180.0},
+                        FieldViolation::OutOfRange{field: LATITUDE, index: 1, value: 91.0, min: -90.0, max: 90.0},
+                        FieldViolation::OutOfRange{field: ELEVATION, index: 3, value: 12000.0, min: -500.0, max: 9000.0}]);
+    }
+
+    #[test]
+    fn test_validate_ranges_reports_other_types() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_number(0, ELEVATION, 100_000).is_ok());
+        assert!(s.add_short_float(0, LATITUDE, 45.5).is_ok());
+        assert!(s.add_number(0, SURFACE_TYPE, 3).is_ok());
+        assert_eq!(validate_ranges(&s),
+                   vec![FieldViolation::WrongType{field: LATITUDE, expected: "LongFloat", got: "ShortFloat"},
+                        FieldViolation::WrongType{field: ELEVATION, expected: "LongFloat", got: "Numbers"}]);
+    }
+}
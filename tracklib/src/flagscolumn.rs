@@ -13,7 +13,7 @@ pub enum Error {
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct FlagsColumn {
     pub(crate) fields: BTreeMap<String, usize>,
     pub(crate) data: BTreeMap<usize, u64>,
@@ -78,20 +78,38 @@ impl FlagsColumn {
         self.max = cmp::max(self.max, index);
     }
 
-    pub fn is_present(&self, index: usize, name: &str) -> bool {
-        if let Some(f) = self.data.get(&index) {
-            if let Some(shift) = self.fields.get(name) {
-                (*f & (1 << *shift)) > 0
-            } else {
-                // no fields present with this name
-                false
-            }
-        } else {
-            // no fields are present for this index
-            false
+    pub(crate) fn field_mask(&self, name: &str) -> u64 {
+        match self.fields.get(name) {
+            Some(shift) => 1 << *shift,
+            None => 0,
         }
+    }
 
+    // Returns a mask with the bit set for every field which is present in
+    // every row. Decoders use this to skip per-row presence checks.
+    pub(crate) fn dense_fields(&self) -> u64 {
+        if !self.data.is_empty() && self.data.len() == self.len() {
+            self.data.values().fold(!0, |mask, row| mask & row)
+        } else {
+            // at least one row has no fields at all
+            0
+        }
+    }
 
+    // Returns the presence bits of one field packed 64 rows to a word (row n
+    // is bit n % 64 of word n / 64), so decoders can walk the present rows
+    // without looking up every row.
+    pub(crate) fn presence_words(&self, name: &str) -> Vec<u64> {
+        let mut words = vec![0; self.len().div_ceil(64)];
+        let mask = self.field_mask(name);
+        if mask != 0 {
+            for (index, row) in self.data.iter() {
+                if row & mask != 0 {
+                    words[index / 64] |= 1 << (index % 64);
+                }
+            }
+        }
+        words
     }
 
     fn bytes_required(&self) -> usize {
@@ -206,6 +224,38 @@ mod tests {
         assert_eq!(c.fields(), vec!["a","b","c","d","e","f","g","h","i","j"]);
     }
 
+    #[test]
+    fn test_dense_fields() {
+        let mut c = FlagsColumn::new();
+        assert_eq!(c.dense_fields(), 0);
+
+        c.set(0, "a");
+        c.set(0, "b");
+        c.set(1, "a");
+        c.set(2, "a");
+        c.set(2, "c");
+        assert_eq!(c.dense_fields(), c.field_mask("a"));
+        assert_eq!(c.field_mask("d"), 0);
+
+        c.set(4, "a");
+        assert_eq!(c.dense_fields(), 0);
+    }
+
+    #[test]
+    fn test_presence_words() {
+        let mut c = FlagsColumn::new();
+        assert!(c.presence_words("a").is_empty());
+
+        c.set(0, "a");
+        c.set(1, "b");
+        c.set(63, "a");
+        c.set(64, "a");
+        c.set(130, "b");
+        assert_eq!(c.presence_words("a"), vec![1 | 1 << 63, 1, 0]);
+        assert_eq!(c.presence_words("b"), vec![1 << 1, 0, 1 << 2]);
+        assert_eq!(c.presence_words("c"), vec![0, 0, 0]);
+    }
+
     #[test]
     fn test_insert_order_matters() {
         // Insert order #1
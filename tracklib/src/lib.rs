@@ -7,10 +7,32 @@ mod flagscolumn;
 mod surface;
 mod polyline;
 mod simplify;
+pub mod fields;
+pub mod edit;
+pub mod stats;
+#[cfg(feature = "mvt")]
+pub mod mvt;
 
-pub use rwtfile::{RWTFMAGIC, RWTFile, DataField};
-pub use metadata::{RWTFMetadata, TrackType};
-pub use section::{Column, SectionType, Section};
-pub use decode::{parse_rwtf};
+pub use rwtfile::{RWTFMAGIC, RWTFile, DataField, Error as RWTFileError};
+pub use metadata::{RWTFMetadata, TrackType, TimeZone};
+pub use section::{Column, ColumnValue, SectionType, Section, Error as SectionError};
+pub use decode::{parse_rwtf, parse_rwtf_strict, verify_rwtf, IntegrityReport, SectionIntegrity};
+pub use decode::{inspect_rwtf, FileLayout, SectionLayout, ColumnLayout, LayoutError};
+pub use decode::{extract_section, remove_sections};
 pub use polyline::{FieldEncodeOptions, PointField};
 pub use surface::{RoadClassMapping, SurfaceMapping};
+
+// Decoded files are plain owned data, so they can be moved to and shared
+// between threads. This fails to compile if that ever stops being true.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<RWTFile>();
+    assert_send_sync::<RWTFMetadata>();
+    assert_send_sync::<TimeZone>();
+    assert_send_sync::<Section>();
+    assert_send_sync::<Column>();
+    assert_send_sync::<DataField>();
+    assert_send_sync::<SurfaceMapping>();
+    assert_send_sync::<FieldEncodeOptions>();
+};
@@ -1,4 +1,5 @@
 use std::io::{Write};
+use std::convert::{TryFrom};
 use snafu::{Snafu, ResultExt};
 use std::time::{UNIX_EPOCH, SystemTime, SystemTimeError};
 use serde::ser::{Error as SerError, Serialize, Serializer, SerializeMap};
@@ -10,6 +11,8 @@ pub enum Error {
     WriteMetadataTable{source: std::io::Error},
     #[snafu(display("Couldn't compute the system time: {}", source))]
     GetTime{source: SystemTimeError},
+    #[snafu(display("Number truncation error: {}", source))]
+    NumberTruncation{source: std::num::TryFromIntError},
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -70,16 +73,71 @@ impl Serialize for TrackType {
     }
 }
 
-#[derive(Debug)]
+// The time zone the track was recorded in. The UTC offset is the one in
+// effect when the track started; the IANA name (e.g. "America/Los_Angeles")
+// is informational since there's no time zone database to resolve it with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeZone {
+    utc_offset: i32,
+    name: Option<String>,
+}
+
+impl TimeZone {
+    pub fn new(utc_offset: i32, name: Option<String>) -> Self {
+        TimeZone{utc_offset, name}
+    }
+
+    // seconds east of UTC
+    pub fn utc_offset(&self) -> i32 {
+        self.utc_offset
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    // Converts a unix timestamp (in seconds) to local time in this zone
+    pub fn local_time(&self, unix_time: i64) -> i64 {
+        unix_time + i64::from(self.utc_offset)
+    }
+
+    // Converts a column of unix timestamps (see `Section::column`) to local times
+    pub fn local_times<'a, I: Iterator<Item = Option<&'a i64>> + 'a>(&'a self, times: I) -> impl Iterator<Item = Option<i64>> + 'a {
+        times.map(move |time| time.map(|time| self.local_time(*time)))
+    }
+}
+
+impl Serialize for TimeZone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("utc_offset", &self.utc_offset)?;
+        if let Some(name) = &self.name {
+            map.serialize_entry("name", name)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct RWTFMetadata {
     created_at: Option<SystemTime>,
     track_type: Option<TrackType>,
+    time_zone: Option<TimeZone>,
 }
 
 impl RWTFMetadata {
     pub(crate) fn new(created_at: Option<SystemTime>, track_type: Option<TrackType>) -> Self {
-        RWTFMetadata{created_at: created_at,
-                     track_type: track_type}
+        RWTFMetadata{created_at,
+                     track_type,
+                     time_zone: None}
+    }
+
+    pub(crate) fn set_time_zone(&mut self, time_zone: Option<TimeZone>) {
+        self.time_zone = time_zone;
+    }
+
+    pub fn time_zone(&self) -> Option<&TimeZone> {
+        self.time_zone.as_ref()
     }
 
     pub fn created_at(&self) -> Option<SystemTime> {
@@ -122,20 +180,35 @@ impl RWTFMetadata {
         Ok(written)
     }
 
+    fn write_time_zone<W: Write>(&self, out: &mut W, time_zone: &TimeZone) -> Result<usize> {
+        let mut written = 0;
+        let name = time_zone.name().unwrap_or("").as_bytes();
+
+        // write the type of the entry: time_zone = 0x02
+        written += write(out, &[0x02]).context(WriteMetadataTable{})?;
+
+        // write size-prefixed entry data: the utc offset followed by the name
+        let entry_size = u16::try_from(4 + name.len()).context(NumberTruncation{})?;
+        written += write(out, &entry_size.to_le_bytes()).context(WriteMetadataTable{})?;
+        written += write(out, &time_zone.utc_offset.to_le_bytes()).context(WriteMetadataTable{})?;
+        written += write(out, name).context(WriteMetadataTable{})?;
+
+        Ok(written)
+    }
+
     pub(crate) fn write<W: Write>(&self, out: &mut W) -> Result<usize> {
         let mut buf = Vec::new();
 
-        if let Some(track_type) = self.track_type {
-            // there are two entries - the track type and created_at
-            write(&mut buf, &[0x02]).context(WriteMetadataTable{})?;
+        // created_at is always written, the other entries are optional
+        let entry_count = 1 + self.track_type.is_some() as u8 + self.time_zone.is_some() as u8;
+        write(&mut buf, &[entry_count]).context(WriteMetadataTable{})?;
 
-            self.write_created_at(&mut buf)?;
+        self.write_created_at(&mut buf)?;
+        if let Some(track_type) = self.track_type {
             self.write_track_type(&mut buf, &track_type)?;
-        } else {
-            // self.track_type isn't set so there is just one entry: created_at
-            write(&mut buf, &[0x01]).context(WriteMetadataTable{})?;
-
-            self.write_created_at(&mut buf)?;
+        }
+        if let Some(time_zone) = &self.time_zone {
+            self.write_time_zone(&mut buf, time_zone)?;
         }
 
         // Write 2 bytes - CRC
@@ -159,6 +232,9 @@ impl Serialize for RWTFMetadata {
         if let Some(track_type) = self.track_type {
             map.serialize_entry("track_type", &track_type)?;
         }
+        if let Some(time_zone) = &self.time_zone {
+            map.serialize_entry("time_zone", time_zone)?;
+        }
         map.end()
     }
 }
@@ -281,4 +357,47 @@ mod tests {
         assert_eq!(m.track_type(), tt);
         assert_eq!(m.track_type().map(|tt| tt.id()), Some(42));
     }
+
+    #[test]
+    fn test_write_metadata_table_with_time_zone() {
+        let mut m = RWTFMetadata::new(None, Some(TrackType::Trip(1)));
+        m.set_time_zone(Some(TimeZone::new(-25200, Some("PDT".to_string()))));
+
+        let mut buf = vec![];
+        let written = m.write(&mut buf);
+        assert!(written.is_ok());
+        let expected_head = &[0x03, // 3 table entries
+                              0x01, // entry #1 is of type created_at
+                              0x08, // entry data is 8 bytes
+                              0x00];
+        let expected_tail = &[0x00, // entry #2 is of type track_type
+                              0x05, // entry data is 5 bytes
+                              0x00,
+                              0x00, // TrackType::Trip
+                              0x01, // the trip id
+                              0x00,
+                              0x00,
+                              0x00,
+                              0x02, // entry #3 is of type time_zone
+                              0x07, // entry data is 7 bytes
+                              0x00,
+                              0x90, // utc offset: -25200
+                              0x9D,
+                              0xFF,
+                              0xFF,
+                              b'P', // the name
+                              b'D',
+                              b'T'];
+        test_buf(&buf, expected_head, expected_tail);
+    }
+
+    #[test]
+    fn test_local_times() {
+        let tz = TimeZone::new(3600, None);
+        assert_eq!(tz.local_time(0), 3600);
+
+        let times = vec![Some(&10), None, Some(&-3600)];
+        assert_eq!(tz.local_times(times.into_iter()).collect::<Vec<_>>(),
+                   vec![Some(3610), None, Some(0)]);
+    }
 }
@@ -0,0 +1,293 @@
+use std::f64::consts::PI;
+use snafu::{Snafu, ResultExt};
+use crate::section::{Section, Error as SectionError};
+use crate::simplify::{simplify_points, Point};
+use crate::surface::{SurfaceMapping};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Tile {}/{}/{} doesn't exist", z, x, y))]
+    InvalidTile{z: u8, x: u32, y: u32},
+    #[snafu(display("Couldn't read coordinates: {}", source))]
+    ReadCoordinates{source: SectionError},
+    #[snafu(display("Couldn't write tile: {}", source))]
+    WriteTile{source: std::io::Error},
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Tile coordinates are integers in 0..EXTENT. Lines are kept up to BUFFER
+// units past the edge of the tile so they join up with the neighboring tiles.
+pub const EXTENT: u32 = 4096;
+const BUFFER: i64 = 64;
+// One pixel of a 256 pixel tile
+const SIMPLIFY_TOLERANCE: f64 = (EXTENT / 256) as f64;
+// Points far outside the tile are pulled in to here, which keeps every delta
+// in range of a sint32 without changing anything inside the tile
+const CLAMP: i64 = 1 << 28;
+// Web Mercator can't show the poles
+const MAX_LATITUDE: f64 = 85.051_128_78;
+
+// An XYZ (slippy map) tile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileCoordinate {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileCoordinate {
+    pub fn new(z: u8, x: u32, y: u32) -> Self {
+        TileCoordinate{z, x, y}
+    }
+
+    // Projects a point with Web Mercator into this tile's coordinates
+    fn project(&self, longitude: f64, latitude: f64) -> (i64, i64) {
+        let scale = 2f64.powi(i32::from(self.z));
+        let latitude = latitude.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+
+        let world_x = (longitude + 180.0) / 360.0 * scale;
+        let world_y = (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0 * scale;
+
+        let x = ((world_x - f64::from(self.x)) * f64::from(EXTENT)).round() as i64;
+        let y = ((world_y - f64::from(self.y)) * f64::from(EXTENT)).round() as i64;
+        (x.clamp(-CLAMP, CLAMP), y.clamp(-CLAMP, CLAMP))
+    }
+}
+
+fn in_buffer(&(x, y): &(i64, i64)) -> bool {
+    let range = -BUFFER..=i64::from(EXTENT) + BUFFER;
+    range.contains(&x) && range.contains(&y)
+}
+
+// Splits the track into the runs of points that fall in the tile, each with
+// one more point on either side so lines cross the tile's edges.
+fn lines_in_tile(points: &[(i64, i64)]) -> Vec<Vec<(i64, i64)>> {
+    let keep = (0..points.len())
+        .map(|i| {
+            in_buffer(&points[i])
+                || (i > 0 && in_buffer(&points[i - 1]))
+                || points.get(i + 1).is_some_and(in_buffer)
+        })
+        .collect::<Vec<_>>();
+
+    let mut lines = vec![];
+    let mut line: Vec<(i64, i64)> = vec![];
+    for (point, keep) in points.iter().zip(keep) {
+        if keep {
+            // zero length segments aren't allowed
+            if line.last() != Some(point) {
+                line.push(*point);
+            }
+        } else if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines.retain(|line| line.len() > 1);
+    lines
+}
+
+fn simplify_line(line: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    let points = line.iter()
+        .enumerate()
+        .map(|(index, &(x, y))| Point{index, x: x as f64, y: y as f64, d: 0.0, e: 0.0, s: None, r: None})
+        .collect::<Vec<_>>();
+
+    let mut kept = simplify_points(&points, &SurfaceMapping::new(0), SIMPLIFY_TOLERANCE)
+        .into_iter()
+        .collect::<Vec<_>>();
+    kept.sort_unstable();
+    kept.into_iter().map(|index| line[index]).collect()
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn command(id: u64, count: usize) -> u64 {
+    (id & 0x7) | ((count as u64) << 3)
+}
+
+// Encodes lines as MoveTo/LineTo commands, with every position relative to
+// the one before it
+fn encode_geometry(lines: &[Vec<(i64, i64)>]) -> Vec<u64> {
+    const MOVE_TO: u64 = 1;
+    const LINE_TO: u64 = 2;
+
+    let mut geometry = vec![];
+    let mut cursor = (0, 0);
+    for line in lines {
+        geometry.push(command(MOVE_TO, 1));
+        geometry.push(zigzag(line[0].0 - cursor.0));
+        geometry.push(zigzag(line[0].1 - cursor.1));
+        cursor = line[0];
+
+        geometry.push(command(LINE_TO, line.len() - 1));
+        for &point in &line[1..] {
+            geometry.push(zigzag(point.0 - cursor.0));
+            geometry.push(zigzag(point.1 - cursor.1));
+            cursor = point;
+        }
+    }
+    geometry
+}
+
+// Protocol buffer encoding, just enough of it for vector tiles. Varints are
+// plain unsigned LEB128.
+fn write_varint_field(out: &mut Vec<u8>, field: u64, v: u64) -> Result<()> {
+    leb128::write::unsigned(out, field << 3).context(WriteTile)?;
+    leb128::write::unsigned(out, v).context(WriteTile)?;
+    Ok(())
+}
+
+fn write_bytes_field(out: &mut Vec<u8>, field: u64, bytes: &[u8]) -> Result<()> {
+    leb128::write::unsigned(out, (field << 3) | 2).context(WriteTile)?;
+    leb128::write::unsigned(out, bytes.len() as u64).context(WriteTile)?;
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn write_packed_field(out: &mut Vec<u8>, field: u64, values: &[u64]) -> Result<()> {
+    let mut buf = vec![];
+    for v in values {
+        leb128::write::unsigned(&mut buf, *v).context(WriteTile)?;
+    }
+    write_bytes_field(out, field, &buf)
+}
+
+// Renders the track in `section` into a Mapbox Vector Tile with a single
+// layer named `layer`, holding one LineString feature if the track crosses
+// the tile. With `simplify`, points that don't change the line by more than
+// a pixel at this zoom are left out.
+pub fn encode_tile(section: &Section, tile: TileCoordinate, layer: &str, simplify: bool) -> Result<Vec<u8>> {
+    if tile.z > 30 || u64::from(tile.x) >= 1 << tile.z || u64::from(tile.y) >= 1 << tile.z {
+        return InvalidTile{z: tile.z, x: tile.x, y: tile.y}.fail();
+    }
+
+    let longitudes = section.longitude();
+    let latitudes = section.latitude();
+    let points = match (longitudes, latitudes) {
+        (Ok(longitudes), Ok(latitudes)) => longitudes.zip(latitudes)
+            .filter_map(|coordinates| match coordinates {
+                (Some(&longitude), Some(&latitude)) => Some(tile.project(longitude, latitude)),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        // no track, so an empty tile
+        (Err(SectionError::MissingColumn{..}), _) | (_, Err(SectionError::MissingColumn{..})) => vec![],
+        (Err(e), _) | (_, Err(e)) => return Err(Error::ReadCoordinates{source: e}),
+    };
+
+    let mut lines = lines_in_tile(&points);
+    if simplify {
+        lines = lines.into_iter().map(simplify_line).collect();
+    }
+
+    let mut layer_buf = vec![];
+    // version = 2
+    write_varint_field(&mut layer_buf, 15, 2)?;
+    write_bytes_field(&mut layer_buf, 1, layer.as_bytes())?;
+    if !lines.is_empty() {
+        let mut feature = vec![];
+        // type = LINESTRING
+        write_varint_field(&mut feature, 3, 2)?;
+        write_packed_field(&mut feature, 4, &encode_geometry(&lines))?;
+        write_bytes_field(&mut layer_buf, 2, &feature)?;
+    }
+    write_varint_field(&mut layer_buf, 5, u64::from(EXTENT))?;
+
+    let mut out = vec![];
+    write_bytes_field(&mut out, 3, &layer_buf)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use crate::section::{SectionType};
+    use crate::fields;
+
+    fn track(points: &[(f64, f64)]) -> Section {
+        let mut s = Section::new(SectionType::TrackPoints);
+        for (i, (x, y)) in points.iter().enumerate() {
+            assert!(s.add_long_float(i, fields::LONGITUDE, *x).is_ok());
+            assert!(s.add_long_float(i, fields::LATITUDE, *y).is_ok());
+        }
+        s
+    }
+
+    #[test]
+    fn test_project() {
+        let tile = TileCoordinate::new(0, 0, 0);
+        assert_eq!(tile.project(-180.0, MAX_LATITUDE), (0, 0));
+        assert_eq!(tile.project(0.0, 0.0), (2048, 2048));
+        assert_eq!(tile.project(180.0, -90.0), (4096, 4096));
+
+        let tile = TileCoordinate::new(1, 1, 0);
+        assert_eq!(tile.project(0.0, 0.0), (0, 4096));
+    }
+
+    #[test]
+    fn test_encode_geometry() {
+        // the example from the vector tile spec
+        assert_eq!(encode_geometry(&[vec![(2, 2), (2, 10), (10, 10)], vec![(1, 1), (3, 5)]]),
+                   vec![9, 4, 4, 18, 0, 16, 16, 0, 9, 17, 17, 10, 4, 8]);
+    }
+
+    #[test]
+    fn test_lines_in_tile() {
+        let points = [(-1000, 10), (10, 10), (10, 20), (-1000, 20), (-2000, 20), (-1000, 30), (20, 30), (20, 30)];
+        assert_eq!(lines_in_tile(&points),
+                   vec![vec![(-1000, 10), (10, 10), (10, 20), (-1000, 20)],
+                        vec![(-1000, 30), (20, 30)]]);
+    }
+
+    #[test]
+    fn test_encode_tile() {
+        let s = track(&[(0.0, 0.0), (10.0, 10.0), (20.0, 0.0), (20.000001, 0.0)]);
+
+        let r = encode_tile(&s, TileCoordinate::new(0, 0, 0), "track", false);
+        assert!(r.is_ok());
+        assert_eq!(r.unwrap(), vec![0x1a, 0x20, // layer
+                                    0x78, 0x02, // version
+                                    0x0a, 0x05, b't', b'r', b'a', b'c', b'k', // name
+                                    0x12, 0x12, // feature
+                                    0x18, 0x02, // type
+                                    0x22, 0x0e, // geometry
+                                    0x09, 0x80, 0x20, 0x80, 0x20, // MoveTo(+2048, +2048)
+                                    0x12, 0xe4, 0x01, 0xe3, 0x01, // LineTo(+114, -114)
+                                    0xe4, 0x01, 0xe4, 0x01, // (+114, +114), the last point is a duplicate
+                                    0x28, 0x80, 0x20]); // extent
+
+        // a tile the track doesn't reach has a layer and no features
+        let r = encode_tile(&s, TileCoordinate::new(4, 0, 0), "track", false);
+        assert!(r.is_ok());
+        assert_eq!(r.unwrap(), vec![0x1a, 0x0c, 0x78, 0x02, 0x0a, 0x05, b't', b'r', b'a', b'c', b'k', 0x28, 0x80, 0x20]);
+
+        assert_matches!(encode_tile(&s, TileCoordinate::new(1, 2, 0), "track", false), Err(Error::InvalidTile{..}));
+    }
+
+    #[test]
+    fn test_encode_tile_simplified() {
+        // a straight line, which simplifies down to its two ends
+        let points = (0..100).map(|i| (f64::from(i) * 0.01, 0.0)).collect::<Vec<_>>();
+        let s = track(&points);
+
+        let full = encode_tile(&s, TileCoordinate::new(10, 512, 511), "track", false).unwrap();
+        let simplified = encode_tile(&s, TileCoordinate::new(10, 512, 511), "track", true).unwrap();
+        assert!(simplified.len() < full.len());
+    }
+
+    #[test]
+    fn test_encode_tile_wrong_coordinate_type() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_short_float(0, fields::LONGITUDE, 1.0).is_ok());
+        assert!(s.add_long_float(0, fields::LATITUDE, 1.0).is_ok());
+        assert_matches!(encode_tile(&s, TileCoordinate::new(0, 0, 0), "track", false), Err(Error::ReadCoordinates{..}));
+    }
+}
@@ -1,8 +1,9 @@
 use snafu::{Snafu, ResultExt};
 use std::io::{Write};
 use std::convert::{TryFrom};
+use std::{panic, thread};
 use crate::section::{Section, SectionType, Error as SectionError};
-use crate::metadata::{RWTFMetadata, TrackType, Error as MetadataError};
+use crate::metadata::{RWTFMetadata, TrackType, TimeZone, Error as MetadataError};
 use crate::utils::{write};
 
 #[derive(Debug, Snafu)]
@@ -25,6 +26,10 @@ pub enum Error {
     WriteTrailer{source: std::io::Error},
     #[snafu(display("Couldn't decode base64: {}", source))]
     DecodeBase64{source: base64::DecodeError},
+    #[snafu(display("Expected a {} value but got a {} value", expected, got))]
+    DataFieldType{expected: &'static str, got: &'static str},
+    #[snafu(display("Column {} can't store value {} at index {}", name, value, index))]
+    UnrepresentableFloat{name: String, index: usize, value: f64},
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -64,6 +69,82 @@ impl From<Vec<u64>> for DataField {
     }
 }
 
+impl DataField {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DataField::Number(_) => "Numbers",
+            DataField::LongFloat(_) => "LongFloat",
+            DataField::ShortFloat(_) => "ShortFloat",
+            DataField::Base64(_) => "Base64",
+            DataField::String(_) => "String",
+            DataField::Bool(_) => "Bool",
+            DataField::IDs(_) => "IDs",
+        }
+    }
+}
+
+macro_rules! try_from_data_field {
+    ($type: ty, $expected: expr, $($variant: path),+) => {
+        impl TryFrom<DataField> for $type {
+            type Error = Error;
+
+            fn try_from(v: DataField) -> Result<Self> {
+                match v {
+                    $($variant(v))|+ => Ok(v),
+                    _ => DataFieldType{expected: $expected, got: v.type_name()}.fail(),
+                }
+            }
+        }
+    }
+}
+
+macro_rules! try_from_data_field_ref {
+    ($type: ty, $expected: expr, $convert: expr, $($variant: path),+) => {
+        impl<'a> TryFrom<&'a DataField> for $type {
+            type Error = Error;
+
+            fn try_from(v: &'a DataField) -> Result<Self> {
+                match v {
+                    $($variant(v))|+ => Ok($convert(v)),
+                    _ => DataFieldType{expected: $expected, got: v.type_name()}.fail(),
+                }
+            }
+        }
+    }
+}
+
+try_from_data_field!(i64, "Numbers", DataField::Number);
+try_from_data_field!(f64, "LongFloat or ShortFloat", DataField::LongFloat, DataField::ShortFloat);
+try_from_data_field!(String, "String", DataField::String);
+try_from_data_field!(bool, "Bool", DataField::Bool);
+try_from_data_field!(Vec<u64>, "IDs", DataField::IDs);
+
+try_from_data_field_ref!(i64, "Numbers", |v: &i64| *v, DataField::Number);
+try_from_data_field_ref!(f64, "LongFloat or ShortFloat", |v: &f64| *v, DataField::LongFloat, DataField::ShortFloat);
+try_from_data_field_ref!(&'a str, "String", String::as_str, DataField::String);
+try_from_data_field_ref!(bool, "Bool", |v: &bool| *v, DataField::Bool);
+try_from_data_field_ref!(&'a [u64], "IDs", Vec::as_slice, DataField::IDs);
+
+// Base64 values hold the encoded text, so they're decoded on the way out
+impl TryFrom<DataField> for Vec<u8> {
+    type Error = Error;
+
+    fn try_from(v: DataField) -> Result<Self> {
+        Self::try_from(&v)
+    }
+}
+
+impl<'a> TryFrom<&'a DataField> for Vec<u8> {
+    type Error = Error;
+
+    fn try_from(v: &'a DataField) -> Result<Self> {
+        match v {
+            DataField::Base64(v) => Ok(base64::decode(v).context(DecodeBase64)?),
+            _ => DataFieldType{expected: "Base64", got: v.type_name()}.fail(),
+        }
+    }
+}
+
 use serde::ser::{Serialize, Serializer, SerializeSeq, SerializeMap};
 
 impl Serialize for DataField {
@@ -101,7 +182,7 @@ pub(crate) const RWTFTRAILER: [u8; 5] = [0xff,  // SectionType
                                          0x57,  // W
                                          0x52]; // R
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RWTFHeader {
     pub(crate) file_version: u8,
     pub(crate) creator_version: u8,
@@ -121,7 +202,7 @@ impl RWTFHeader {
         self.creator_version
     }
 
-    fn write<W: Write>(&self, out: &mut W, metadata_table_offset: u16, data_offset: u16) -> Result<usize> {
+    pub(crate) fn write<W: Write>(&self, out: &mut W, metadata_table_offset: u16, data_offset: u16) -> Result<usize> {
         let mut buf = Vec::with_capacity(24);
 
         // Write 8 bytes - Magic Number
@@ -159,6 +240,20 @@ impl RWTFHeader {
     }
 }
 
+const PARALLEL_WRITE_MIN_POINTS: usize = 10_000;
+
+const LONG_FLOAT_SCALE: f64 = 10000000.0;
+const SHORT_FLOAT_SCALE: f64 = 1000.0;
+
+// Floats are stored as deltas between integers scaled by `scale`, which can't
+// represent NaN or infinity. Scaled values are kept within ±2^62 so that the
+// delta between any two of them still fits in an i64.
+fn fits_scaled(v: f64, scale: f64) -> bool {
+    const LIMIT: f64 = (1u64 << 62) as f64;
+    let scaled = v * scale;
+    scaled > -LIMIT && scaled < LIMIT
+}
+
 #[derive(Debug)]
 pub struct RWTFile {
     pub(crate) header: RWTFHeader,
@@ -188,6 +283,8 @@ impl RWTFile {
 
     fn add_point<V: Into<DataField>>(section: &mut Section, index: usize, k: &str, v: V) -> Result<()>{
         match v.into() {
+            DataField::LongFloat(v) if !fits_scaled(v, LONG_FLOAT_SCALE) => UnrepresentableFloat{name: k, index, value: v}.fail(),
+            DataField::ShortFloat(v) if !fits_scaled(v, SHORT_FLOAT_SCALE) => UnrepresentableFloat{name: k, index, value: v}.fail(),
             DataField::Number(v) => section.add_number(index, k, v).eager_context(AddTrackPoint),
             DataField::LongFloat(v) => section.add_long_float(index, k, v).eager_context(AddTrackPoint),
             DataField::ShortFloat(v) => section.add_short_float(index, k, v).eager_context(AddTrackPoint),
@@ -210,20 +307,47 @@ impl RWTFile {
         &self.metadata
     }
 
+    pub fn set_time_zone(&mut self, time_zone: Option<TimeZone>) {
+        self.metadata.set_time_zone(time_zone);
+    }
+
+    fn write_section(section: &Section) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        if section.len() > 0 {
+            section.write(&mut buf).context(WriteSection)?;
+        }
+        Ok(buf)
+    }
+
+    fn parallel_write(&self) -> bool {
+        // Spawning a thread only pays off when both sections are large. Most
+        // files have far fewer course points than track points, and those are
+        // written on one thread.
+        self.track_points.len().min(self.course_points.len()) >= PARALLEL_WRITE_MIN_POINTS
+    }
+
+    fn write_sections(&self, parallel: bool) -> Result<(Vec<u8>, Vec<u8>)> {
+        let (track_points_buf, course_points_buf) = if parallel {
+            // Sections are encoded independently, so the course points can
+            // be encoded on another thread while this one does the track points
+            thread::scope(|s| {
+                let course_points = s.spawn(|| Self::write_section(&self.course_points));
+                let track_points = Self::write_section(&self.track_points);
+                (track_points, course_points.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            })
+        } else {
+            (Self::write_section(&self.track_points), Self::write_section(&self.course_points))
+        };
+
+        Ok((track_points_buf?, course_points_buf?))
+    }
+
     pub fn write<W: Write>(&self, out: &mut W) -> Result<usize> {
         // Prepare all the data
         let mut metadata_table_buf = vec![];
         self.metadata.write(&mut metadata_table_buf).context(WriteMetadataTable)?;
 
-        let mut track_points_buf = vec![];
-        if self.track_points.len() > 0 {
-            self.track_points.write(&mut track_points_buf).context(WriteSection)?;
-        }
-
-        let mut course_points_buf = vec![];
-        if self.course_points.len() > 0 {
-            self.course_points.write(&mut course_points_buf).context(WriteSection)?;
-        }
+        let (track_points_buf, course_points_buf) = self.write_sections(self.parallel_write())?;
 
         let header_size: u16 = 24;
         let metadata_table_offset: u16 = header_size;
@@ -259,6 +383,7 @@ impl Serialize for RWTFile {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_matches::assert_matches;
 
     #[test]
     fn testfoo() {
@@ -275,6 +400,38 @@ mod tests {
         assert!(f.add_track_point(1, "bam", DataField::ShortFloat(0.3)).is_ok());
     }
 
+    #[test]
+    fn test_try_from_data_field() {
+        assert_eq!(i64::try_from(DataField::Number(5)).ok(), Some(5));
+        assert_eq!(f64::try_from(DataField::LongFloat(0.5)).ok(), Some(0.5));
+        assert_eq!(f64::try_from(DataField::ShortFloat(1.5)).ok(), Some(1.5));
+        assert_eq!(String::try_from(DataField::String("hey".into())).ok(), Some("hey".to_string()));
+        assert_eq!(bool::try_from(DataField::Bool(true)).ok(), Some(true));
+        assert_eq!(Vec::<u64>::try_from(DataField::IDs(vec![1, 2])).ok(), Some(vec![1, 2]));
+        assert_eq!(Vec::<u8>::try_from(DataField::Base64("SGk=".into())).ok(), Some(b"Hi".to_vec()));
+
+        assert_matches!(i64::try_from(DataField::String("5".into())),
+                        Err(Error::DataFieldType{expected: "Numbers", got: "String"}));
+        assert_matches!(Vec::<u8>::try_from(DataField::Base64("invalid base64".into())),
+                        Err(Error::DecodeBase64{..}));
+        assert_eq!(format!("{}", bool::try_from(DataField::Number(1)).unwrap_err()),
+                   "Expected a Bool value but got a Numbers value");
+    }
+
+    #[test]
+    fn test_try_from_data_field_ref() {
+        let s = DataField::String("hey".into());
+        let ids = DataField::IDs(vec![3, 4]);
+        assert_eq!(<&str>::try_from(&s).ok(), Some("hey"));
+        assert_eq!(<&[u64]>::try_from(&ids).ok(), Some(&[3, 4][..]));
+        assert_eq!(i64::try_from(&DataField::Number(-2)).ok(), Some(-2));
+        assert_eq!(f64::try_from(&DataField::ShortFloat(2.5)).ok(), Some(2.5));
+        assert_eq!(bool::try_from(&DataField::Bool(false)).ok(), Some(false));
+
+        assert_matches!(<&str>::try_from(&ids), Err(Error::DataFieldType{expected: "String", got: "IDs"}));
+        assert_matches!(f64::try_from(&s), Err(Error::DataFieldType{expected: "LongFloat or ShortFloat", got: "String"}));
+    }
+
     #[test]
     fn test_base64() {
         let mut f = RWTFile::new();
@@ -282,6 +439,75 @@ mod tests {
         assert!(f.add_track_point(1, "foo", DataField::Base64("invalid base64".into())).is_err());
     }
 
+    #[test]
+    fn test_unrepresentable_floats() {
+        let mut f = RWTFile::new();
+        assert_matches!(f.add_track_point(0, "x", DataField::LongFloat(f64::NAN)), Err(Error::UnrepresentableFloat{index: 0, ..}));
+        assert_matches!(f.add_track_point(1, "x", DataField::LongFloat(f64::INFINITY)), Err(Error::UnrepresentableFloat{index: 1, ..}));
+        assert_matches!(f.add_course_point(2, "d", DataField::ShortFloat(f64::NEG_INFINITY)), Err(Error::UnrepresentableFloat{index: 2, ..}));
+        // finite, but too large once scaled
+        assert_matches!(f.add_track_point(3, "x", DataField::LongFloat(1e12)), Err(Error::UnrepresentableFloat{index: 3, ..}));
+        assert_matches!(f.add_track_point(3, "d", DataField::ShortFloat(-1e16)), Err(Error::UnrepresentableFloat{index: 3, ..}));
+        // in range of an i64 once scaled, but not once subtracted from an
+        // opposite value
+        assert_matches!(f.add_track_point(3, "d", DataField::ShortFloat(9e15)), Err(Error::UnrepresentableFloat{index: 3, ..}));
+        assert_matches!(f.add_track_point(3, "x", DataField::LongFloat(-9e11)), Err(Error::UnrepresentableFloat{index: 3, ..}));
+        assert!(f.add_track_point(1, "d", DataField::ShortFloat(-1e15)).is_ok());
+        assert!(f.add_track_point(0, "x", DataField::LongFloat(1.5)).is_ok());
+        assert_eq!(f.track_points.len(), 2);
+        assert_eq!(f.course_points.len(), 0);
+
+        // the largest values that are accepted can still be written next to
+        // each other
+        let mut f = RWTFile::new();
+        assert!(f.add_track_point(0, "d", DataField::ShortFloat(4.6e15)).is_ok());
+        assert!(f.add_track_point(1, "d", DataField::ShortFloat(-4.6e15)).is_ok());
+        assert!(f.add_track_point(0, "x", DataField::LongFloat(4.6e11)).is_ok());
+        assert!(f.add_track_point(1, "x", DataField::LongFloat(-4.6e11)).is_ok());
+        assert!(f.add_track_point(2, "x", DataField::LongFloat(4.6e11)).is_ok());
+        let mut buf = vec![];
+        assert!(f.write(&mut buf).is_ok());
+    }
+
+    #[test]
+    fn test_parallel_write() {
+        let mut f = RWTFile::new();
+        for i in 0..PARALLEL_WRITE_MIN_POINTS {
+            assert!(f.add_track_point(i, "t", i as i64).is_ok());
+        }
+        assert!(!f.parallel_write());
+
+        // A typical file: many track points and a handful of course points
+        assert!(f.add_course_point(0, "n", DataField::String("Turn left".to_string())).is_ok());
+        assert!(!f.parallel_write());
+
+        for i in 1..PARALLEL_WRITE_MIN_POINTS {
+            assert!(f.add_course_point(i, "n", DataField::String("Turn left".to_string())).is_ok());
+        }
+        assert!(f.parallel_write());
+    }
+
+    #[test]
+    fn test_parallel_write_sections() {
+        let mut f = RWTFile::new();
+        for i in 0..PARALLEL_WRITE_MIN_POINTS {
+            assert!(f.add_track_point(i, "t", i as i64).is_ok());
+            assert!(f.add_course_point(i, "n", DataField::String(i.to_string())).is_ok());
+        }
+
+        let sequential = f.write_sections(false);
+        let parallel = f.write_sections(true);
+        assert!(sequential.is_ok());
+        assert!(parallel.is_ok());
+
+        let (sequential_track_points, sequential_course_points) = sequential.unwrap();
+        let (parallel_track_points, parallel_course_points) = parallel.unwrap();
+        assert!(!parallel_track_points.is_empty());
+        assert!(!parallel_course_points.is_empty());
+        assert_eq!(sequential_track_points, parallel_track_points);
+        assert_eq!(sequential_course_points, parallel_course_points);
+    }
+
     #[test]
     fn test_write_header() {
         let f = RWTFHeader::new();
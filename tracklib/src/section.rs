@@ -1,20 +1,22 @@
 use std::io::{Write};
-use snafu::{Snafu, ResultExt};
+use snafu::{Snafu, OptionExt, ResultExt};
 use std::collections::btree_map::{self, BTreeMap};
 use std::convert::{TryFrom};
 use std::cmp;
+use itertools::Itertools;
 use serde::ser::{Serialize, Serializer, SerializeSeq, SerializeMap};
 use crate::rwtfile::{DataField};
 use crate::flagscolumn::{self, FlagsColumn};
-use crate::utils::{write};
+use crate::utils::{write, CRC32Writer};
 use crate::polyline::FieldEncodeOptions;
 use crate::simplify::simplify_and_encode;
 use crate::surface::SurfaceMapping;
+use crate::fields;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
-    #[snafu(display("Column {} tried to change type", name))]
-    ColumnTypeChange{name: String},
+    #[snafu(display("Column {} tried to change type from {} to {} at index {}", name, expected, got, index))]
+    ColumnTypeChange{name: String, index: usize, expected: &'static str, got: &'static str},
     #[snafu(display("Column {} tried to reused index {}", name, index))]
     IndexAlreadyUsed{name: String, index: usize},
     #[snafu(display("Couldn't write types table: {}", source))]
@@ -31,12 +33,16 @@ pub enum Error {
     WriteDataColumnNumberOfPoints{},
     #[snafu(display("Number truncation error: {}", source))]
     NumberTruncation{source: std::num::TryFromIntError},
+    #[snafu(display("Column {} doesn't exist", name))]
+    MissingColumn{name: String},
+    #[snafu(display("Column {} isn't a {} column", name, expected))]
+    ColumnTypeMismatch{name: String, expected: &'static str},
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Column {
     Numbers(BTreeMap<usize, i64>),
     LongFloat(BTreeMap<usize, f64>),
@@ -59,8 +65,49 @@ impl Column {
             Column::IDs(_)        => 0x06,
         }
     }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Column::Numbers(_)    => "Numbers",
+            Column::LongFloat(_)  => "LongFloat",
+            Column::ShortFloat(_) => "ShortFloat",
+            Column::Base64(_)     => "Base64",
+            Column::String(_)     => "String",
+            Column::Bool(_)       => "Bool",
+            Column::IDs(_)        => "IDs",
+        }
+    }
+}
+
+// A Rust type which a column's values can be read as, see Section::column()
+pub trait ColumnValue: Sized + 'static {
+    const TYPE_NAME: &'static str;
+
+    fn values(column: &Column) -> Option<&BTreeMap<usize, Self>>;
 }
 
+macro_rules! column_value {
+    ($type: ty, $name: expr, $($variant: path),+) => {
+        impl ColumnValue for $type {
+            const TYPE_NAME: &'static str = $name;
+
+            fn values(column: &Column) -> Option<&BTreeMap<usize, Self>> {
+                match column {
+                    $($variant(m))|+ => Some(m),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+column_value!(i64, "Numbers", Column::Numbers);
+column_value!(f64, "LongFloat or ShortFloat", Column::LongFloat, Column::ShortFloat);
+column_value!(Vec<u8>, "Base64", Column::Base64);
+column_value!(String, "String", Column::String);
+column_value!(bool, "Bool", Column::Bool);
+column_value!(Vec<u64>, "IDs", Column::IDs);
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SectionType {
     TrackPoints,
@@ -88,7 +135,7 @@ impl SectionType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Section {
     pub(crate) section_type: SectionType,
     pub(crate) max: usize,
@@ -96,8 +143,18 @@ pub struct Section {
     pub(crate) columns: BTreeMap<String, Column>,
 }
 
+fn interpolate_gaps<I: Iterator<Item = (usize, f64)>>(values: I, max_gap: usize) -> Vec<(usize, f64)> {
+    values
+        .tuple_windows()
+        .filter(|((a, _), (b, _))| b - a > 1 && b - a - 1 <= max_gap)
+        .flat_map(|((a, va), (b, vb))| {
+            ((a + 1)..b).map(move |i| (i, va + (vb - va) * (i - a) as f64 / (b - a) as f64))
+        })
+        .collect()
+}
+
 macro_rules! add_x {
-    ($name: ident, $variant: path, $type: ty) => {
+    ($name: ident, $variant: path, $type: ty, $field: path) => {
         pub(crate) fn $name(&mut self, index: usize, k: &str, v: $type) -> Result<()> {
             // N.B. It would be nicer to use the entry API here, but in
             // this case, that would require allocating a new String for
@@ -123,7 +180,13 @@ macro_rules! add_x {
                             }
                         },
                         _ => {
-                            ColumnTypeChange{name: k}.fail()
+                            // v isn't always stored as the DataField holds
+                            // it (Base64 is decoded), so name its variant
+                            // with a placeholder value instead
+                            ColumnTypeChange{name: k,
+                                             index,
+                                             expected: column.type_name(),
+                                             got: $field(Default::default()).type_name()}.fail()
                         }
                     }
                 },
@@ -148,13 +211,13 @@ impl Section {
                 columns: BTreeMap::new()}
     }
 
-    add_x!(add_number, Column::Numbers, i64);
-    add_x!(add_long_float, Column::LongFloat, f64);
-    add_x!(add_short_float, Column::ShortFloat, f64);
-    add_x!(add_base64, Column::Base64, Vec<u8>);
-    add_x!(add_string, Column::String, String);
-    add_x!(add_bool, Column::Bool, bool);
-    add_x!(add_ids, Column::IDs, Vec<u64>);
+    add_x!(add_number, Column::Numbers, i64, DataField::Number);
+    add_x!(add_long_float, Column::LongFloat, f64, DataField::LongFloat);
+    add_x!(add_short_float, Column::ShortFloat, f64, DataField::ShortFloat);
+    add_x!(add_base64, Column::Base64, Vec<u8>, DataField::Base64);
+    add_x!(add_string, Column::String, String, DataField::String);
+    add_x!(add_bool, Column::Bool, bool, DataField::Bool);
+    add_x!(add_ids, Column::IDs, Vec<u64>, DataField::IDs);
 
     pub fn len(&self) -> usize {
         self.flags.len()
@@ -168,6 +231,133 @@ impl Section {
         &self.columns
     }
 
+    // Returns the values of column `name` for every point in this section,
+    // with None where a point has no value. The column type is checked once,
+    // up front. The iterator can be reversed to read from the last point.
+    pub fn column<'a, T: ColumnValue>(&'a self, name: &str) -> Result<impl DoubleEndedIterator<Item = Option<&'a T>> + ExactSizeIterator + 'a> {
+        let column = self.columns.get(name).context(MissingColumn{name})?;
+        let m = T::values(column).context(ColumnTypeMismatch{name, expected: T::TYPE_NAME})?;
+
+        Ok((0..self.len()).map(move |index| m.get(&index)))
+    }
+
+    // Like `column`, but each absent value is replaced with the last present
+    // value before it. Points before the first present value are still None.
+    pub fn column_forward_filled<'a, T: ColumnValue>(&'a self, name: &str) -> Result<impl ExactSizeIterator<Item = Option<&'a T>> + 'a> {
+        let mut last = None;
+        Ok(self.column::<T>(name)?.map(move |v| {
+            if v.is_some() {
+                last = v;
+            }
+            last
+        }))
+    }
+
+    // Like `column::<f64>`, but only LongFloat columns are accepted, since
+    // that's what the well known coordinate fields are stored as (see
+    // `fields`) and what simplification expects.
+    pub(crate) fn long_float_column(&self, name: &str) -> Result<impl DoubleEndedIterator<Item = Option<&f64>> + ExactSizeIterator + '_> {
+        let column = self.columns.get(name).context(MissingColumn{name})?;
+        let m = match column {
+            Column::LongFloat(m) => m,
+            _ => return ColumnTypeMismatch{name, expected: "LongFloat"}.fail(),
+        };
+
+        Ok((0..self.len()).map(move |index| m.get(&index)))
+    }
+
+    pub fn longitude(&self) -> Result<impl DoubleEndedIterator<Item = Option<&f64>> + ExactSizeIterator + '_> {
+        self.long_float_column(fields::LONGITUDE)
+    }
+
+    pub fn latitude(&self) -> Result<impl DoubleEndedIterator<Item = Option<&f64>> + ExactSizeIterator + '_> {
+        self.long_float_column(fields::LATITUDE)
+    }
+
+    pub fn elevation(&self) -> Result<impl DoubleEndedIterator<Item = Option<&f64>> + ExactSizeIterator + '_> {
+        self.long_float_column(fields::ELEVATION)
+    }
+
+    // Returns a copy of this section with the gaps in numeric column `name`
+    // filled in by linear interpolation. Only gaps of at most `max_gap`
+    // points between two present values are filled, leading and trailing
+    // gaps are left alone. Filled points are marked true in the Bool column
+    // `marker`.
+    pub fn interpolate(&self, name: &str, max_gap: usize, marker: &str) -> Result<Section> {
+        let column = self.columns.get(name).context(MissingColumn{name})?;
+        let mut section = self.clone();
+
+        match column {
+            Column::Numbers(m) => {
+                for (index, v) in interpolate_gaps(m.iter().map(|(i, v)| (*i, *v as f64)), max_gap) {
+                    section.add_number(index, name, v.round() as i64)?;
+                    section.add_bool(index, marker, true)?;
+                }
+            }
+            Column::LongFloat(m) => {
+                for (index, v) in interpolate_gaps(m.iter().map(|(i, v)| (*i, *v)), max_gap) {
+                    section.add_long_float(index, name, v)?;
+                    section.add_bool(index, marker, true)?;
+                }
+            }
+            Column::ShortFloat(m) => {
+                for (index, v) in interpolate_gaps(m.iter().map(|(i, v)| (*i, *v)), max_gap) {
+                    section.add_short_float(index, name, v)?;
+                    section.add_bool(index, marker, true)?;
+                }
+            }
+            _ => return ColumnTypeMismatch{name, expected: "Numbers, LongFloat or ShortFloat"}.fail(),
+        }
+
+        Ok(section)
+    }
+
+    // Returns a copy of this section with only the points for which `keep`
+    // returns true. The points that are left are renumbered so there are no
+    // gaps, and columns left without any values are dropped.
+    pub(crate) fn retain_points<F: FnMut(usize) -> bool>(&self, mut keep: F) -> Result<Section> {
+        let mut next = 0;
+        let indexes = (0..self.len())
+            .map(|index| {
+                if keep(index) {
+                    next += 1;
+                    Some(next - 1)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut section = Section::new(self.section_type);
+
+        macro_rules! retain {
+            ($m: expr, $name: expr, $add: ident) => {
+                for (index, v) in $m.iter() {
+                    if let Some(&Some(new_index)) = indexes.get(*index) {
+                        section.$add(new_index, $name, Clone::clone(v))?;
+                    }
+                }
+            }
+        }
+
+        // Columns are added in their original order, which keeps the types
+        // table in the same order too
+        for name in self.flags.fields() {
+            match self.columns.get(name) {
+                Some(Column::Numbers(m)) => retain!(m, name, add_number),
+                Some(Column::LongFloat(m)) => retain!(m, name, add_long_float),
+                Some(Column::ShortFloat(m)) => retain!(m, name, add_short_float),
+                Some(Column::Base64(m)) => retain!(m, name, add_base64),
+                Some(Column::String(m)) => retain!(m, name, add_string),
+                Some(Column::Bool(m)) => retain!(m, name, add_bool),
+                Some(Column::IDs(m)) => retain!(m, name, add_ids),
+                None => {}
+            }
+        }
+
+        Ok(section)
+    }
+
     pub fn simplify_and_encode(&self, mapping: &SurfaceMapping, tolerance: f64, fields: &[FieldEncodeOptions]) -> String {
         simplify_and_encode(self, mapping, tolerance, fields)
     }
@@ -202,7 +392,8 @@ impl Section {
     }
 
     fn write_data<W: Write>(&self, out: &mut W) -> Result<usize> {
-        let mut buf = Vec::new();
+        // The CRC is computed as the columns are written
+        let mut buf = CRC32Writer::new(Vec::new());
 
         // Write the "Flags" column
         self.flags.write(&mut buf).context(WriteFlagsColumn)?;
@@ -313,7 +504,8 @@ impl Section {
         }
 
         // Write 4 bytes - Data CRC
-        let crc = crc::crc32::checksum_ieee(&buf).to_le_bytes();
+        let crc = buf.crc().to_le_bytes();
+        let mut buf = buf.into_inner();
         write(&mut buf, &crc).with_context(|| WriteDataColumn{name: "crc"})?;
 
         // Write buf -> out
@@ -418,6 +610,7 @@ impl Serialize for Section {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_matches::assert_matches;
     use crate::polyline::PointField;
 
     #[test]
@@ -454,6 +647,18 @@ mod tests {
         assert!(s.add_number(3, "foo", 0).is_ok());
     }
 
+    #[test]
+    fn test_column_type_change_error() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_number(0, "foo", 0).is_ok());
+        assert_matches!(s.add_string(7, "foo", "bar".to_string()),
+                        Err(Error::ColumnTypeChange{ref name, index: 7, expected: "Numbers", got: "String"}) if name == "foo");
+        assert_eq!(format!("{}", s.add_bool(8, "foo", true).unwrap_err()),
+                   "Column foo tried to change type from Numbers to Bool at index 8");
+        assert_matches!(s.add_base64(9, "foo", vec![1]),
+                        Err(Error::ColumnTypeChange{got: "Base64", ..}));
+    }
+
     #[test]
     fn test_len() {
         let mut s = Section::new(SectionType::TrackPoints);
@@ -466,6 +671,120 @@ mod tests {
         assert_eq!(s.len(), 501);
     }
 
+    #[test]
+    fn test_typed_column() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_number(0, "a", 5).is_ok());
+        assert!(s.add_number(2, "a", 7).is_ok());
+        assert!(s.add_short_float(1, "b", 0.5).is_ok());
+        assert!(s.add_string(2, "c", "hi".to_string()).is_ok());
+
+        let a = s.column::<i64>("a");
+        assert!(a.is_ok());
+        assert_eq!(a.unwrap().collect::<Vec<_>>(), vec![Some(&5), None, Some(&7)]);
+
+        let b = s.column::<f64>("b");
+        assert!(b.is_ok());
+        assert_eq!(b.unwrap().collect::<Vec<_>>(), vec![None, Some(&0.5), None]);
+
+        let c = s.column::<String>("c");
+        assert!(c.is_ok());
+        assert_eq!(c.unwrap().flatten().collect::<Vec<_>>(), vec!["hi"]);
+
+        assert!(s.column::<bool>("a").is_err());
+        assert!(s.column::<i64>("z").is_err());
+    }
+
+    #[test]
+    fn test_typed_column_rev() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_long_float(0, "x", 1.5).is_ok());
+        assert!(s.add_long_float(1, "x", 2.5).is_ok());
+        assert!(s.add_number(3, "t", 9).is_ok());
+
+        let x = s.column::<f64>("x");
+        assert!(x.is_ok());
+        let x = x.unwrap();
+        assert_eq!(x.len(), 4);
+        assert_eq!(x.rev().collect::<Vec<_>>(), vec![None, None, Some(&2.5), Some(&1.5)]);
+
+        // last known value
+        assert_eq!(s.column::<f64>("x").unwrap().rev().flatten().next(), Some(&2.5));
+    }
+
+    #[test]
+    fn test_column_forward_filled() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_number(1, "hr", 120).is_ok());
+        assert!(s.add_number(4, "hr", 125).is_ok());
+        assert!(s.add_string(5, "name", "a".into()).is_ok());
+
+        let hr = s.column_forward_filled::<i64>("hr");
+        assert!(hr.is_ok());
+        let hr = hr.unwrap();
+        assert_eq!(hr.len(), 6);
+        assert_eq!(hr.collect::<Vec<_>>(), vec![None, Some(&120), Some(&120), Some(&120), Some(&125), Some(&125)]);
+
+        assert_matches!(s.column_forward_filled::<i64>("name").err(), Some(Error::ColumnTypeMismatch{..}));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_number(0, "t", 0).is_ok());
+        assert!(s.add_number(3, "t", 3).is_ok());
+        assert!(s.add_number(8, "t", 8).is_ok());
+        assert!(s.add_long_float(0, "x", 1.0).is_ok());
+        assert!(s.add_long_float(2, "x", 2.0).is_ok());
+        assert!(s.add_long_float(9, "x", 9.0).is_ok());
+
+        let t = s.interpolate("t", 2, "ti");
+        assert!(t.is_ok());
+        let t = t.unwrap();
+        assert_eq!(t.column::<i64>("t").unwrap().collect::<Vec<_>>(),
+                   vec![Some(&0), Some(&1), Some(&2), Some(&3), None, None, None, None, Some(&8), None]);
+        assert_eq!(t.column::<bool>("ti").unwrap().collect::<Vec<_>>(),
+                   vec![None, Some(&true), Some(&true), None, None, None, None, None, None, None]);
+
+        let x = s.interpolate("x", 1, "xi").unwrap();
+        assert_eq!(x.column::<f64>("x").unwrap().take(3).collect::<Vec<_>>(),
+                   vec![Some(&1.0), Some(&1.5), Some(&2.0)]);
+        assert_eq!(x.column::<bool>("xi").unwrap().flatten().count(), 1);
+
+        // the original section is untouched
+        assert_eq!(s.column::<i64>("t").unwrap().flatten().count(), 3);
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_string(0, "name", "a".into()).is_ok());
+        assert!(s.add_number(0, "t", 0).is_ok());
+        assert!(s.add_number(2, "t", 2).is_ok());
+
+        assert_matches!(s.interpolate("foo", 1, "fi").err(), Some(Error::MissingColumn{..}));
+        assert_matches!(s.interpolate("name", 1, "ni").err(), Some(Error::ColumnTypeMismatch{..}));
+        assert_matches!(s.interpolate("t", 1, "name").err(), Some(Error::ColumnTypeChange{..}));
+    }
+
+    #[test]
+    fn test_well_known_fields() {
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_long_float(0, fields::LONGITUDE, -122.5).is_ok());
+        assert!(s.add_long_float(0, fields::LATITUDE, 45.5).is_ok());
+        assert!(s.add_number(1, fields::ELEVATION, 10).is_ok());
+
+        assert_eq!(s.longitude().unwrap().collect::<Vec<_>>(), vec![Some(&-122.5), None]);
+        assert_eq!(s.latitude().unwrap().collect::<Vec<_>>(), vec![Some(&45.5), None]);
+        assert_matches!(s.elevation().err(), Some(Error::ColumnTypeMismatch{expected: "LongFloat", ..}));
+
+        // coordinates are always LongFloat, a ShortFloat column is rejected
+        let mut s = Section::new(SectionType::TrackPoints);
+        assert!(s.add_short_float(0, fields::LONGITUDE, -122.5).is_ok());
+        assert!(s.column::<f64>(fields::LONGITUDE).is_ok());
+        assert_matches!(s.longitude().err(), Some(Error::ColumnTypeMismatch{expected: "LongFloat", ..}));
+    }
+
     #[test]
     fn test_write_types_table() {
         let mut s = Section::new(SectionType::TrackPoints);
@@ -1,17 +1,18 @@
 use crate::polyline::{polyline_encode, FieldEncodeOptions};
 use crate::surface::{RoadClassId, SurfaceMapping, SurfaceTypeId};
 use crate::{Column, Section};
+use crate::fields;
 use itertools::Itertools;
 use std::collections::{BTreeMap, HashSet};
 
-fn haversine_distance(prev: &Point, x: f64, y: f64) -> f64 {
+pub(crate) fn haversine_distance(prev_x: f64, prev_y: f64, x: f64, y: f64) -> f64 {
     // lifted wholesale from https://github.com/georust/geo/blob/2cf153d59072d18054baf4da8bcaf3e0c088a7d8/geo/src/algorithm/haversine_distance.rs
     const MEAN_EARTH_RADIUS: f64 = 6_371_000.0;
 
-    let theta1 = prev.y.to_radians();
+    let theta1 = prev_y.to_radians();
     let theta2 = y.to_radians();
-    let delta_theta = (y - prev.y).to_radians();
-    let delta_lambda = (x - prev.x).to_radians();
+    let delta_theta = (y - prev_y).to_radians();
+    let delta_lambda = (x - prev_x).to_radians();
     let a = (delta_theta / 2.0).sin().powi(2) + theta1.cos() * theta2.cos() * (delta_lambda / 2.0).sin().powi(2);
     let c = 2.0 * a.sqrt().asin();
     MEAN_EARTH_RADIUS * c
@@ -180,7 +181,7 @@ impl<'a, 'b> Iterator for SurfaceGroupIter<'a, 'b> {
     }
 }
 
-fn simplify_points(points: &[Point], mapping: &SurfaceMapping, tolerance: f64) -> HashSet<usize> {
+pub(crate) fn simplify_points(points: &[Point], mapping: &SurfaceMapping, tolerance: f64) -> HashSet<usize> {
     fn stack_rdp(points: &[Point], tolerance_sq: f64) -> HashSet<usize> {
         let mut anchors = HashSet::new();
         let mut stack = Vec::new();
@@ -214,7 +215,7 @@ fn section_to_points(section: &Section) -> Vec<Point> {
     let empty_base64_btree = BTreeMap::new();
 
     let columns = section.columns();
-    let x_map = if let Some(x_column) = columns.get("x") {
+    let x_map = if let Some(x_column) = columns.get(fields::LONGITUDE) {
         match x_column {
             Column::LongFloat(x) => x,
             _ => panic!("unexpected x column type"),
@@ -223,7 +224,7 @@ fn section_to_points(section: &Section) -> Vec<Point> {
         &empty_longfloat_btree
     };
 
-    let y_map = if let Some(y_column) = columns.get("y") {
+    let y_map = if let Some(y_column) = columns.get(fields::LATITUDE) {
         match y_column {
             Column::LongFloat(y) => y,
             _ => panic!("unexpected y column type"),
@@ -232,7 +233,7 @@ fn section_to_points(section: &Section) -> Vec<Point> {
         &empty_longfloat_btree
     };
 
-    let e_map = if let Some(e_column) = columns.get("e") {
+    let e_map = if let Some(e_column) = columns.get(fields::ELEVATION) {
         match e_column {
             Column::LongFloat(e) => e,
             _ => panic!("unexpected e column type"),
@@ -241,7 +242,7 @@ fn section_to_points(section: &Section) -> Vec<Point> {
         &empty_longfloat_btree
     };
 
-    let s_map = if let Some(s_column) = columns.get("S") {
+    let s_map = if let Some(s_column) = columns.get(fields::SURFACE_TYPE) {
         match s_column {
             Column::Numbers(s) => s,
             _ => panic!("unexpected S column type"),
@@ -250,7 +251,7 @@ fn section_to_points(section: &Section) -> Vec<Point> {
         &empty_numbers_btree
     };
 
-    let r_map = if let Some(r_column) = columns.get("R") {
+    let r_map = if let Some(r_column) = columns.get(fields::ROAD_CLASS) {
         match r_column {
             Column::Numbers(r) => r,
             _ => panic!("unexpected R column type"),
@@ -283,7 +284,7 @@ fn section_to_points(section: &Section) -> Vec<Point> {
 
         if let (Some(x), Some(y), Some(e), None) = (x, y, e, ep) {
             let d = if let Some(prev) = points.last() {
-                prev.d + haversine_distance(prev, *x, *y)
+                prev.d + haversine_distance(prev.x, prev.y, *x, *y)
             } else {
                 0.0
             };
@@ -0,0 +1,95 @@
+use std::convert::{TryFrom};
+
+use snafu::{Snafu, ResultExt};
+use crate::section::{Section, Error as SectionError};
+use crate::fields;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Couldn't read column {}: {}", name, source))]
+    ReadColumn{name: String, source: SectionError},
+    #[snafu(display("Zone thresholds must be strictly increasing"))]
+    UnsortedThresholds{},
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+// Returns the seconds spent in each zone of `field`, a Numbers column such as
+// heart rate or power. `thresholds` are the lower bounds of every zone but the
+// first, so there is one more zone than thresholds. The time between two
+// consecutive points with a `fields::TIME` counts toward the zone of the value
+// at the first of them. Intervals starting at a point without a value aren't
+// counted anywhere, and neither are points without a time.
+pub fn zones(section: &Section, field: &str, thresholds: &[i64]) -> Result<Vec<u64>> {
+    if thresholds.windows(2).any(|w| w[0] >= w[1]) {
+        return UnsortedThresholds{}.fail();
+    }
+
+    let times = section.column::<i64>(fields::TIME).context(ReadColumn{name: fields::TIME})?;
+    let values = section.column::<i64>(field).context(ReadColumn{name: field})?;
+
+    let mut seconds = vec![0; thresholds.len() + 1];
+    let mut previous: Option<(i64, Option<i64>)> = None;
+    for (time, value) in times.zip(values) {
+        if let Some(&time) = time {
+            if let Some((previous_time, Some(previous_value))) = previous {
+                // thresholds are sorted, so this is the index of the zone
+                let zone = thresholds.iter().take_while(|&&t| t <= previous_value).count();
+                // a clock that went backwards doesn't take time away
+                seconds[zone] += u64::try_from(time - previous_time).unwrap_or(0);
+            }
+            previous = Some((time, value.copied()));
+        }
+    }
+
+    Ok(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use crate::rwtfile::{RWTFile, DataField};
+
+    #[test]
+    fn test_zones() {
+        let mut f = RWTFile::new();
+        let points: &[(Option<i64>, Option<i64>)] = &[(Some(0), Some(100)),
+                                                      (Some(10), Some(150)),
+                                                      (Some(15), None),
+                                                      (Some(45), Some(170)),
+                                                      (None, Some(90)),
+                                                      (Some(50), Some(130)),
+                                                      (Some(70), Some(100))];
+        for (i, &(time, hr)) in points.iter().enumerate() {
+            if let Some(time) = time {
+                assert!(f.add_track_point(i, fields::TIME, time).is_ok());
+            }
+            if let Some(hr) = hr {
+                assert!(f.add_track_point(i, "hr", hr).is_ok());
+            }
+        }
+
+        // 0-10 at 100, 10-15 at 150, 15-45 has no value, 45-50 at 170 (the
+        // point without a time is skipped), 50-70 at 130
+        assert_eq!(zones(&f.track_points, "hr", &[120, 140, 160]).unwrap(), vec![10, 20, 5, 5]);
+        // a value equal to a threshold is in the zone above it
+        assert_eq!(zones(&f.track_points, "hr", &[100]).unwrap(), vec![0, 40]);
+        assert_eq!(zones(&f.track_points, "hr", &[]).unwrap(), vec![40]);
+    }
+
+    #[test]
+    fn test_zones_errors() {
+        let mut f = RWTFile::new();
+        assert!(f.add_track_point(0, fields::TIME, 0).is_ok());
+        assert!(f.add_track_point(0, "power", 200).is_ok());
+        assert!(f.add_track_point(0, "cadence", DataField::LongFloat(1.5)).is_ok());
+
+        assert_matches!(zones(&f.track_points, "power", &[100, 100]), Err(Error::UnsortedThresholds{}));
+        assert_matches!(zones(&f.track_points, "hr", &[100]),
+                        Err(Error::ReadColumn{source: SectionError::MissingColumn{..}, ..}));
+        assert_matches!(zones(&f.track_points, "cadence", &[100]),
+                        Err(Error::ReadColumn{source: SectionError::ColumnTypeMismatch{..}, ..}));
+        assert_eq!(zones(&f.track_points, "power", &[100]).unwrap(), vec![0, 0]);
+    }
+}
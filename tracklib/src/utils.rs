@@ -1,6 +1,60 @@
 use std::io::{Write, Result};
+use crc::crc32;
 
 pub(crate) fn write<W: Write>(out: &mut W, bytes: &[u8]) -> Result<usize> {
     out.write_all(bytes)?;
     Ok(bytes.len())
 }
+
+// Wraps a writer and keeps a running CRC32 (IEEE) of everything written
+// through it, so callers don't need a second pass over the finished buffer.
+pub(crate) struct CRC32Writer<W> {
+    inner: W,
+    crc: u32,
+}
+
+impl<W: Write> CRC32Writer<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        CRC32Writer{inner,
+                    crc: 0}
+    }
+
+    pub(crate) fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CRC32Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc = crc32::update(self.crc, &crc32::IEEE_TABLE, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_writer() {
+        let mut w = CRC32Writer::new(Vec::new());
+        assert_eq!(w.crc(), crc32::checksum_ieee(&[]));
+
+        assert!(write(&mut w, b"hello").is_ok());
+        assert!(write(&mut w, b", ").is_ok());
+        assert!(leb128::write::signed(&mut w, -5000).is_ok());
+        assert_eq!(w.crc(), crc32::checksum_ieee(w.inner.as_slice()));
+
+        let buf = w.into_inner();
+        assert!(buf.starts_with(b"hello, "));
+    }
+}